@@ -0,0 +1,5 @@
+-- Backfill `status` for historical entries and make it mandatory
+UPDATE subscriptions
+  SET status = 'confirmed'
+  WHERE status IS NULL;
+ALTER TABLE subscriptions ALTER COLUMN status SET NOT NULL;
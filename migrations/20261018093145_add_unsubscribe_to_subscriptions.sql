@@ -0,0 +1,8 @@
+-- Per-subscriber token used in unsubscribe links, plus when the subscriber left the list
+ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL UNIQUE;
+ALTER TABLE subscriptions ADD COLUMN unsubscribed_at TIMESTAMPTZ NULL;
+
+UPDATE subscriptions
+  SET unsubscribe_token = replace(gen_random_uuid()::text, '-', '')
+  WHERE unsubscribe_token IS NULL;
+ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
@@ -0,0 +1,8 @@
+-- One row per (issue, subscriber) pair still waiting to be delivered
+CREATE TABLE issue_delivery_queue (
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  n_retries INT NOT NULL DEFAULT 0,
+  execute_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
@@ -0,0 +1,15 @@
+-- Saved responses for requests carrying an `Idempotency-Key` header
+CREATE TYPE header_pair AS (
+  name TEXT,
+  value BYTEA
+);
+
+CREATE TABLE idempotency (
+  idempotency_key TEXT NOT NULL,
+  -- NULL while the first request with this key is still being processed
+  response_status_code SMALLINT NULL,
+  response_headers header_pair[] NULL,
+  response_body BYTEA NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (idempotency_key)
+);
@@ -0,0 +1,6 @@
+-- Idempotency keys are only unique per user. Saved responses are short-lived, so we can drop the
+-- ones saved before requests were authenticated rather than backfilling an owner.
+DELETE FROM idempotency;
+ALTER TABLE idempotency ADD COLUMN user_id uuid NOT NULL REFERENCES users (user_id);
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_pkey;
+ALTER TABLE idempotency ADD PRIMARY KEY (user_id, idempotency_key);
@@ -0,0 +1,7 @@
+-- Server-side state for admin sessions, the cookie only carries the session key
+CREATE TABLE sessions (
+  session_key TEXT NOT NULL,
+  PRIMARY KEY (session_key),
+  state JSONB NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL
+);
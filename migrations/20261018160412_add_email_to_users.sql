@@ -0,0 +1,2 @@
+-- Where password reset links are sent, optional so existing users keep working
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
@@ -0,0 +1,9 @@
+-- Only a SHA-256 hash of each token is stored, the token itself is only ever sent by email
+CREATE TABLE password_reset_tokens (
+  token_hash TEXT NOT NULL,
+  PRIMARY KEY (token_hash),
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ NOT NULL,
+  used_at TIMESTAMPTZ NULL
+);
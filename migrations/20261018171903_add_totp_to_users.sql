@@ -0,0 +1,5 @@
+-- Base32 TOTP secret, set when enrollment starts but only enforced once a code was verified.
+-- The last accepted time step is kept so that a code can't be replayed within its window.
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT NULL;
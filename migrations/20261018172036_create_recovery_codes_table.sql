@@ -0,0 +1,7 @@
+-- One-time codes to get past the second factor without the authenticator, stored hashed
+CREATE TABLE recovery_codes (
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  code_hash TEXT NOT NULL,
+  PRIMARY KEY (user_id, code_hash),
+  used_at TIMESTAMPTZ NULL
+);
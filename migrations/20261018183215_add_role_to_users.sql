@@ -0,0 +1,9 @@
+-- What an admin user is allowed to do, existing users keep full access
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+  CHECK (role IN ('viewer', 'editor', 'publisher', 'owner'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+
+-- Users can now be deleted, their saved idempotent responses go with them
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_user_id_fkey;
+ALTER TABLE idempotency ADD CONSTRAINT idempotency_user_id_fkey
+  FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE;
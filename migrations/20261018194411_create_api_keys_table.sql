@@ -0,0 +1,15 @@
+-- Keys for machine-to-machine access. Only a SHA-256 hash of the key is stored, the prefix is
+-- kept in clear so that keys can be told apart. Keys act on behalf of the user who created them.
+CREATE TABLE api_keys (
+  api_key_id uuid NOT NULL,
+  PRIMARY KEY (api_key_id),
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  prefix TEXT NOT NULL UNIQUE,
+  key_hash TEXT NOT NULL UNIQUE,
+  scopes TEXT[] NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ NULL,
+  last_used_at TIMESTAMPTZ NULL,
+  revoked_at TIMESTAMPTZ NULL
+);
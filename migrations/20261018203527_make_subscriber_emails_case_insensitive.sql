@@ -0,0 +1,42 @@
+-- Addresses are now stored with a lowercase, punycode domain. Existing rows get their domain
+-- lowercased here, and uniqueness ignores case so that `Foo@example.com` and `foo@example.com`
+-- are the same subscriber.
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+
+UPDATE subscriptions
+SET email = split_part(email, '@', 1) || '@' || lower(split_part(email, '@', 2))
+WHERE email LIKE '%@%' AND email NOT LIKE '%@%@%';
+
+-- Rows that only differ by case are merged into one before the index is built: the confirmed
+-- one if there is one, otherwise the oldest. The others hand over their confirmation tokens and
+-- pending deliveries, then go.
+CREATE TEMPORARY TABLE subscriber_merges ON COMMIT DROP AS
+SELECT duplicate_id, keeper_id
+FROM (
+  SELECT
+    id AS duplicate_id,
+    first_value(id) OVER (
+      PARTITION BY lower(email)
+      ORDER BY status = 'confirmed' DESC, subscribed_at, id
+    ) AS keeper_id
+  FROM subscriptions
+) AS ranked
+WHERE duplicate_id <> keeper_id;
+
+UPDATE subscription_tokens t
+SET subscriber_id = m.keeper_id
+FROM subscriber_merges m
+WHERE t.subscriber_id = m.duplicate_id;
+
+INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_id, n_retries, execute_after)
+SELECT DISTINCT ON (q.newsletter_issue_id, m.keeper_id)
+  q.newsletter_issue_id, m.keeper_id, q.n_retries, q.execute_after
+FROM issue_delivery_queue q
+JOIN subscriber_merges m ON q.subscriber_id = m.duplicate_id
+ORDER BY q.newsletter_issue_id, m.keeper_id, q.execute_after
+ON CONFLICT DO NOTHING;
+
+-- The duplicates' queue entries go with them (ON DELETE CASCADE)
+DELETE FROM subscriptions WHERE id IN (SELECT duplicate_id FROM subscriber_merges);
+
+CREATE UNIQUE INDEX subscriptions_email_lower_key ON subscriptions (lower(email));
@@ -0,0 +1,8 @@
+-- Admin-managed exceptions to the bundled disposable domain list. A rule applies to the domain
+-- and all of its subdomains.
+CREATE TABLE email_domain_rules (
+  domain TEXT NOT NULL,
+  PRIMARY KEY (domain),
+  rule TEXT NOT NULL CHECK (rule IN ('block', 'allow')),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,37 @@
+-- Newsletters run from this deployment. Everything that existed before belongs to the default
+-- `newsletter` list.
+CREATE TABLE lists (
+  list_id uuid NOT NULL,
+  PRIMARY KEY (list_id),
+  slug TEXT NOT NULL UNIQUE,
+  name TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+INSERT INTO lists (list_id, slug, name) VALUES (gen_random_uuid(), 'newsletter', 'Newsletter');
+
+-- Per-list opt-in state. `subscriptions.status` becomes a summary of the memberships: confirmed
+-- if any is, pending if any is, unsubscribed otherwise.
+CREATE TABLE list_memberships (
+  list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  PRIMARY KEY (list_id, subscriber_id),
+  status TEXT NOT NULL
+    CHECK (status IN ('pending_confirmation', 'confirmed', 'unsubscribed')),
+  subscribed_at TIMESTAMPTZ NOT NULL,
+  unsubscribed_at TIMESTAMPTZ NULL
+);
+CREATE INDEX list_memberships_subscriber_id_idx ON list_memberships (subscriber_id);
+INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at, unsubscribed_at)
+SELECT l.list_id, s.id, s.status, s.subscribed_at, s.unsubscribed_at
+FROM subscriptions s, lists l
+WHERE l.slug = 'newsletter';
+
+-- A confirmation link confirms a single list
+ALTER TABLE subscription_tokens
+  ADD COLUMN list_id uuid NULL REFERENCES lists (list_id) ON DELETE CASCADE;
+UPDATE subscription_tokens SET list_id = (SELECT list_id FROM lists WHERE slug = 'newsletter');
+ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+ALTER TABLE newsletter_issues ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE newsletter_issues SET list_id = (SELECT list_id FROM lists WHERE slug = 'newsletter');
+ALTER TABLE newsletter_issues ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,4 @@
+-- Custom attributes configured per deployment, keyed by attribute name. Dates are stored as
+-- `YYYY-MM-DD` strings.
+ALTER TABLE subscriptions ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE newsletter_issues ADD COLUMN segment TEXT NULL;
@@ -0,0 +1,15 @@
+-- Free-form labels editors put on subscribers, e.g. `beta` or `conference-2026`
+CREATE TABLE tags (
+  tag_id uuid NOT NULL,
+  PRIMARY KEY (tag_id),
+  name TEXT NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE subscriber_tags (
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  tag_id uuid NOT NULL REFERENCES tags (tag_id) ON DELETE CASCADE,
+  PRIMARY KEY (subscriber_id, tag_id),
+  tagged_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX subscriber_tags_tag_id_idx ON subscriber_tags (tag_id);
@@ -0,0 +1,16 @@
+-- One row per bulk import, keeping the rejected rows for download
+CREATE TABLE subscriber_imports (
+  import_id uuid NOT NULL,
+  PRIMARY KEY (import_id),
+  -- NULL for imports run from the command line
+  created_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+  file_name TEXT NOT NULL,
+  list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+  status TEXT NOT NULL,
+  n_created INTEGER NOT NULL,
+  n_updated INTEGER NOT NULL,
+  n_rejected INTEGER NOT NULL,
+  -- CSV with a line, email and reason column
+  rejected_report TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
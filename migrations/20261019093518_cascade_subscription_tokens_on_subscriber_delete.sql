@@ -0,0 +1,5 @@
+-- Tokens go away with their subscriber, like every other table referencing `subscriptions`
+ALTER TABLE subscription_tokens
+  DROP CONSTRAINT subscription_tokens_subscriber_id_fkey,
+  ADD CONSTRAINT subscription_tokens_subscriber_id_fkey
+    FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE CASCADE;
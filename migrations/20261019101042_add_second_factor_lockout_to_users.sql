@@ -0,0 +1,5 @@
+-- Wrong second factor codes in a row, kept on the user rather than in the session so that
+-- logging in again with the password doesn't reset them. Reaching the limit locks the second
+-- step until `second_factor_locked_until`.
+ALTER TABLE users ADD COLUMN failed_second_factor_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN second_factor_locked_until TIMESTAMPTZ NULL;
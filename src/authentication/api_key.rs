@@ -0,0 +1,323 @@
+use std::{future::Future, marker::PhantomData, pin::Pin};
+
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, ResponseError,
+    dev::Payload,
+    http::{StatusCode, header::HeaderMap},
+    web,
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::{
+    role::{Role, get_role},
+    tokens::{generate_token, hash_token},
+};
+use crate::problem_details::{ProblemDetails, error_chain_fmt};
+
+const API_KEY_PREFIX: &str = "z2p";
+
+/// What an API key is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    SubscribersWrite,
+    NewslettersPublish,
+    StatsRead,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 3] = [
+        ApiScope::SubscribersWrite,
+        ApiScope::NewslettersPublish,
+        ApiScope::StatsRead,
+    ];
+
+    pub fn parse(s: &str) -> Result<ApiScope> {
+        match s {
+            "subscribers:write" => Ok(ApiScope::SubscribersWrite),
+            "newsletters:publish" => Ok(ApiScope::NewslettersPublish),
+            "stats:read" => Ok(ApiScope::StatsRead),
+            _ => bail!("{} is not a valid scope.", s),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::SubscribersWrite => "subscribers:write",
+            ApiScope::NewslettersPublish => "newsletters:publish",
+            ApiScope::StatsRead => "stats:read",
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A valid, unrevoked and unexpired API key
+#[derive(Debug)]
+pub struct ApiKey {
+    pub api_key_id: Uuid,
+    /// The user who created the key, requests made with it act on their behalf
+    pub user_id: Uuid,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// Issues a new key, returning its id and the key itself. The key can't be recovered later.
+#[instrument(name = "Create API key", skip(pool))]
+pub async fn create_api_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(Uuid, String)> {
+    let api_key_id = Uuid::new_v4();
+    let prefix = format!("{}_{}", API_KEY_PREFIX, generate_token(8));
+    let key = format!("{}_{}", prefix, generate_token(32));
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_keys (api_key_id, user_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        api_key_id,
+        user_id,
+        name,
+        prefix,
+        hash_token(&key),
+        &scopes,
+        expires_at,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store API key")?;
+
+    Ok((api_key_id, key))
+}
+
+/// Returns whether a key was revoked
+#[instrument(name = "Revoke API key", skip(pool))]
+pub async fn revoke_api_key(pool: &PgPool, api_key_id: Uuid) -> Result<bool> {
+    let n_revoked = sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET revoked_at = now()
+        WHERE api_key_id = $1 AND revoked_at IS NULL
+        "#,
+        api_key_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke API key")?
+    .rows_affected();
+    Ok(n_revoked > 0)
+}
+
+/// Looks up a key and records that it was used, `None` if it is unknown, revoked or expired
+#[instrument(name = "Authenticate API key", skip(pool, key))]
+pub async fn authenticate_api_key(pool: &PgPool, key: &str) -> Result<Option<ApiKey>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET last_used_at = now()
+        WHERE key_hash = $1
+          AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > now())
+        RETURNING api_key_id, user_id, scopes
+        "#,
+        hash_token(key),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up API key")?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let scopes = row
+        .scopes
+        .iter()
+        // scopes that were dropped since the key was issued are ignored
+        .filter_map(|s| ApiScope::parse(s).ok())
+        .collect();
+    Ok(Some(ApiKey {
+        api_key_id: row.api_key_id,
+        user_id: row.user_id,
+        scopes,
+    }))
+}
+
+/// Extracts the key from an `Authorization: Bearer ...` header
+pub fn bearer_token(headers: &HeaderMap) -> Result<&str> {
+    headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?
+        .strip_prefix("Bearer ")
+        .context("The authorization scheme was not 'Bearer'.")
+}
+
+/// Scope checked by a [`RequireScope`] guard, and the role the key's creator must still have
+pub trait ScopeRequirement {
+    const SCOPE: ApiScope;
+    const MIN_ROLE: Role;
+}
+
+pub struct SubscribersWrite;
+pub struct NewslettersPublish;
+pub struct StatsRead;
+
+impl ScopeRequirement for SubscribersWrite {
+    const SCOPE: ApiScope = ApiScope::SubscribersWrite;
+    const MIN_ROLE: Role = Role::Editor;
+}
+
+impl ScopeRequirement for NewslettersPublish {
+    const SCOPE: ApiScope = ApiScope::NewslettersPublish;
+    const MIN_ROLE: Role = Role::Publisher;
+}
+
+impl ScopeRequirement for StatsRead {
+    const SCOPE: ApiScope = ApiScope::StatsRead;
+    const MIN_ROLE: Role = Role::Viewer;
+}
+
+/// Why a request was refused a bearer API key
+#[derive(thiserror::Error)]
+pub enum ApiAuthError {
+    #[error("No API key was provided")]
+    MissingKey(#[source] anyhow::Error),
+    #[error("Unknown, revoked or expired API key")]
+    InvalidKey,
+    #[error("The API key lacks the {0} scope")]
+    MissingScope(ApiScope),
+    #[error("The creator of the API key lacks the {0} role")]
+    MissingRole(Role),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ApiAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiAuthError::MissingKey(_) | ApiAuthError::InvalidKey => StatusCode::UNAUTHORIZED,
+            ApiAuthError::MissingScope(_) | ApiAuthError::MissingRole(_) => StatusCode::FORBIDDEN,
+            ApiAuthError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let (problem, challenge) = match self {
+            ApiAuthError::MissingKey(_) => (
+                ProblemDetails::new(status, "missing_api_key")
+                    .detail("Send an API key as `Authorization: Bearer <key>`."),
+                Some("Bearer".to_string()),
+            ),
+            ApiAuthError::InvalidKey => (
+                ProblemDetails::new(status, "invalid_api_key")
+                    .detail("The API key is unknown, revoked or expired."),
+                Some(r#"Bearer error="invalid_token""#.to_string()),
+            ),
+            ApiAuthError::MissingScope(scope) => (
+                ProblemDetails::new(status, "insufficient_scope")
+                    .detail(format!("The API key lacks the {} scope.", scope)),
+                Some(format!(
+                    r#"Bearer error="insufficient_scope", scope="{}""#,
+                    scope
+                )),
+            ),
+            ApiAuthError::MissingRole(role) => (
+                ProblemDetails::new(status, "insufficient_role").detail(format!(
+                    "The user who created the API key must be at least {} to use it.",
+                    role
+                )),
+                None,
+            ),
+            ApiAuthError::Unexpected(_) => (ProblemDetails::new(status, "internal_error"), None),
+        };
+        let mut builder = problem.response_builder();
+        if let Some(challenge) = challenge {
+            builder.insert_header(("WWW-Authenticate", challenge));
+        }
+        builder.json(&problem)
+    }
+}
+
+/// Authenticates a bearer key and checks that it carries the scope of `S`, and that its creator
+/// still has the role `S` requires: keys act on the creator's behalf, so they stop working when
+/// the creator is demoted.
+pub async fn authorize_api_key<S: ScopeRequirement>(
+    pool: &PgPool,
+    key: &str,
+) -> Result<ApiKey, ApiAuthError> {
+    let api_key = authenticate_api_key(pool, key)
+        .await?
+        .ok_or(ApiAuthError::InvalidKey)?;
+    if !api_key.has_scope(S::SCOPE) {
+        return Err(ApiAuthError::MissingScope(S::SCOPE));
+    }
+    match get_role(api_key.user_id, pool).await? {
+        Some(role) if role >= S::MIN_ROLE => Ok(api_key),
+        _ => Err(ApiAuthError::MissingRole(S::MIN_ROLE)),
+    }
+}
+
+/// Guard for API routes, requires a bearer API key with the given scope. Missing or invalid keys
+/// get a 401, keys without the scope or whose creator lacks the role a 403.
+pub struct RequireScope<S> {
+    pub api_key: ApiKey,
+    _scope: PhantomData<S>,
+}
+
+impl<S: ScopeRequirement> FromRequest for RequireScope<S> {
+    type Error = ApiAuthError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = bearer_token(req.headers()).map(str::to_string);
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        Box::pin(async move {
+            let token = token.map_err(ApiAuthError::MissingKey)?;
+            let pool = pool.expect("The connection pool is registered as app data");
+            let api_key = authorize_api_key::<S>(&pool, &token).await?;
+            Ok(RequireScope {
+                api_key,
+                _scope: PhantomData,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiScope;
+
+    #[test]
+    fn scopes_round_trip_through_their_names() {
+        for scope in ApiScope::ALL {
+            assert_eq!(ApiScope::parse(scope.as_str()).unwrap(), scope);
+        }
+        assert!(ApiScope::parse("subscribers:delete").is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::ops::Deref;
+
+use actix_web::{
+    FromRequest, HttpMessage,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    middleware::Next,
+    web,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::role::get_role;
+use crate::{session::TypedSession, utils::see_other};
+
+/// Id of the logged in user, available to handlers behind [`reject_anonymous_users`] through
+/// `web::ReqData<UserId>`, along with their [`super::Role`]
+#[derive(Copy, Clone, Debug)]
+pub struct UserId(Uuid);
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Deref for UserId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Redirects requests without a logged in user to the login page, or to the second login step
+/// when only the password has been checked so far. Sessions of deleted users are ended.
+pub async fn reject_anonymous_users(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    let internal_error = |e| {
+        let response = actix_web::HttpResponse::InternalServerError().finish();
+        InternalError::from_response(e, response)
+    };
+    match session.get_user_id().map_err(internal_error)? {
+        Some(user_id) => {
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .expect("The connection pool is registered as app data")
+                .clone();
+            let role = get_role(user_id, &pool).await.map_err(|e| {
+                let response = actix_web::HttpResponse::InternalServerError().finish();
+                InternalError::from_response(e, response)
+            })?;
+            let Some(role) = role else {
+                session.log_out();
+                return Ok(req.into_response(see_other("/login")).map_into_right_body());
+            };
+            req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(role);
+            next.call(req).await.map(|res| res.map_into_left_body())
+        }
+        None => {
+            let location = match session.get_pending_user_id().map_err(internal_error)? {
+                Some(_) => "/login/two-factor",
+                None => "/login",
+            };
+            Ok(req.into_response(see_other(location)).map_into_right_body())
+        }
+    }
+}
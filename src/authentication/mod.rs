@@ -0,0 +1,30 @@
+mod api_key;
+mod basic;
+mod middleware;
+mod password;
+mod password_reset;
+mod role;
+mod tokens;
+mod totp;
+
+pub use api_key::{
+    ApiAuthError, ApiKey, ApiScope, NewslettersPublish, RequireScope, ScopeRequirement, StatsRead,
+    SubscribersWrite, authenticate_api_key, authorize_api_key, bearer_token, create_api_key,
+    revoke_api_key,
+};
+pub use basic::basic_authentication;
+pub use middleware::{UserId, reject_anonymous_users};
+pub use password::{
+    AuthError, Credentials, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH, change_password,
+    compute_password_hash, create_user, ensure_user, validate_credentials, validate_new_password,
+};
+pub use password_reset::{
+    PASSWORD_RESET_TOKEN_TTL, check_password_reset_token, consume_password_reset_token,
+    create_password_reset_token,
+};
+pub use role::{Editor, Owner, Publisher, RequireRole, Role, RoleRequirement, get_role};
+pub use totp::{
+    RECOVERY_CODES_COUNT, SECOND_FACTOR_LOCKOUT, SecondFactorOutcome, TotpState, disable_totp,
+    enable_totp, get_totp_state, provisioning_uri, qr_code_svg, regenerate_recovery_codes,
+    start_totp_enrollment, verify_second_factor,
+};
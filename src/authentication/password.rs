@@ -0,0 +1,309 @@
+use anyhow::{Context, Result, bail};
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash::SaltString,
+};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tracing::{Span, info, instrument};
+use uuid::Uuid;
+
+use super::Role;
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Checks the credentials against the `users` table, returning the id of the matching user.
+///
+/// Unknown usernames are checked against a dummy hash, so that both failure modes take the same
+/// time and don't leak which usernames exist.
+#[instrument(name = "Validate credentials", skip(credentials, pool))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = Secret::new(
+        "$argon2id$v=19$m=19456,t=2,p=1$\
+        gZiV/M1gPc22ElAH/Jh1Hw$\
+        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+            .to_string(),
+    );
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, pool).await?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .context("Failed to spawn blocking task.")??;
+
+    // only reachable with a stored hash, a password can't match the dummy one
+    user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username."))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+/// Hashes a password into an Argon2id PHC string, with OWASP's recommended parameters
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!(e))?;
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(19456, 2, 1, None).map_err(|e| anyhow::anyhow!(e))?,
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)
+    .map_err(|e| anyhow::anyhow!(e))?
+    .to_string();
+    Ok(Secret::new(password_hash))
+}
+
+/// Creates the user if no user with that name exists yet, used to bootstrap the first admin as
+/// an owner
+#[instrument(name = "Ensure user exists", skip(password, pool))]
+pub async fn ensure_user(
+    username: &str,
+    password: Secret<String>,
+    email: Option<&str>,
+    pool: &PgPool,
+) -> Result<()> {
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, 'owner')
+        ON CONFLICT (username) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        username,
+        password_hash.expose_secret(),
+        email,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store user")?
+    .rows_affected();
+
+    if n_inserted_rows > 0 {
+        info!("Created user {}", username);
+    }
+    Ok(())
+}
+
+/// Creates a new user, returning `None` if the username or email is already taken
+#[instrument(name = "Create user", skip(password, pool))]
+pub async fn create_user(
+    username: &str,
+    password: Secret<String>,
+    email: Option<&str>,
+    role: Role,
+    pool: &PgPool,
+) -> Result<Option<Uuid>> {
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+
+    let user_id = Uuid::new_v4();
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        email,
+        role.as_str(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store user")?
+    .rows_affected();
+
+    Ok((n_inserted_rows > 0).then_some(user_id))
+}
+
+/// Shortest password accepted when setting a new one
+pub const MIN_PASSWORD_LENGTH: usize = 12;
+/// Longest password accepted when setting a new one, hashing unbounded input is a DoS vector
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// Checks a new password against the strength rules and its confirmation field, the error
+/// messages are meant to be shown to the user
+pub fn validate_new_password(
+    password: &Secret<String>,
+    password_check: &Secret<String>,
+) -> Result<()> {
+    let password = password.expose_secret();
+    if password != password_check.expose_secret() {
+        bail!("You entered two different new passwords - the field values must match.");
+    }
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        bail!(
+            "The new password must be at least {} characters long.",
+            MIN_PASSWORD_LENGTH
+        );
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        bail!(
+            "The new password must be at most {} characters long.",
+            MAX_PASSWORD_LENGTH
+        );
+    }
+    if password.chars().all(|c| c.is_alphabetic()) || password.chars().all(|c| c.is_numeric()) {
+        bail!("The new password must mix letters with digits or symbols.");
+    }
+    Ok(())
+}
+
+/// Replaces the password of a user, the caller is expected to have validated it
+#[instrument(name = "Change password", skip(password, executor))]
+pub async fn change_password<'c>(
+    user_id: Uuid,
+    password: Secret<String>,
+    executor: impl sqlx::PgExecutor<'c>,
+) -> Result<()> {
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to change user's password in the database.")?;
+    Ok(())
+}
+
+#[instrument(name = "Get stored credentials", skip(username, pool))]
+async fn get_stored_credentials(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, password_hash
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve stored credentials.")?
+    .map(|row| (row.user_id, Secret::new(row.password_hash)));
+
+    Ok(row)
+}
+
+#[instrument(
+    name = "Verify password hash",
+    skip(expected_password_hash, password_candidate)
+)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+
+    // the parameters are read from the PHC string, not from this instance
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password.")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+/// Argon2 is CPU-bound on purpose, keep it off the async executor while staying in the caller's
+/// span
+fn spawn_blocking_with_tracing<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::{PasswordHash, PasswordVerifier};
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::{MAX_PASSWORD_LENGTH, compute_password_hash, validate_new_password};
+
+    fn check(password: &str, password_check: &str) -> anyhow::Result<()> {
+        validate_new_password(
+            &Secret::new(password.to_string()),
+            &Secret::new(password_check.to_string()),
+        )
+    }
+
+    #[test]
+    fn password_hashes_are_argon2id_phc_strings() {
+        let hash = compute_password_hash(Secret::new("hunter2".to_string())).unwrap();
+        let hash = PasswordHash::new(hash.expose_secret()).unwrap();
+
+        assert_eq!(hash.algorithm.as_str(), "argon2id");
+        assert!(
+            argon2::Argon2::default()
+                .verify_password(b"hunter2", &hash)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn a_long_mixed_password_is_accepted() {
+        assert!(check("correct horse battery 9", "correct horse battery 9").is_ok());
+    }
+
+    #[test]
+    fn mismatched_confirmation_is_rejected() {
+        assert!(check("correct horse battery 9", "correct horse battery 8").is_err());
+    }
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert!(check("s3cr3t!", "s3cr3t!").is_err());
+    }
+
+    #[test]
+    fn overly_long_passwords_are_rejected() {
+        let password = "a1".repeat(MAX_PASSWORD_LENGTH);
+        assert!(check(&password, &password).is_err());
+    }
+
+    #[test]
+    fn letters_only_passwords_are_rejected() {
+        assert!(check("onlylettershere", "onlylettershere").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::tokens::{generate_token, hash_token};
+
+/// How long a password reset link stays valid
+pub const PASSWORD_RESET_TOKEN_TTL: Duration = Duration::hours(1);
+
+/// Issues a new reset token for the user, only its hash is stored
+#[instrument(name = "Create password reset token", skip(pool))]
+pub async fn create_password_reset_token(pool: &PgPool, user_id: Uuid) -> Result<String> {
+    let token = generate_token(43);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        hash_token(&token),
+        user_id,
+        Utc::now() + PASSWORD_RESET_TOKEN_TTL,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store password reset token")?;
+
+    Ok(token)
+}
+
+/// Returns the user the token was issued to, if it is still valid
+#[instrument(name = "Check password reset token", skip(token, pool))]
+pub async fn check_password_reset_token(pool: &PgPool, token: &str) -> Result<Option<Uuid>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM password_reset_tokens
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up password reset token")?;
+
+    Ok(row.map(|r| r.user_id))
+}
+
+/// Marks the token as used and returns the user it was issued to, if it was still valid. Every
+/// other outstanding token of that user is burnt along with it.
+#[instrument(name = "Consume password reset token", skip(token, transaction))]
+pub async fn consume_password_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<Uuid>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to consume password reset token")?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        row.user_id,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to invalidate outstanding password reset tokens")?;
+
+    Ok(Some(row.user_id))
+}
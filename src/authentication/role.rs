@@ -0,0 +1,131 @@
+use std::{
+    future::{Ready, ready},
+    marker::PhantomData,
+};
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, dev::Payload};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What an admin user is allowed to do, each role can do everything the previous ones can
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only access to the dashboard and subscribers
+    Viewer,
+    /// Can manage subscribers
+    Editor,
+    /// Can publish newsletter issues
+    Publisher,
+    /// Can manage admin users
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Viewer, Role::Editor, Role::Publisher, Role::Owner];
+
+    pub fn parse(s: &str) -> Result<Role> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "publisher" => Ok(Role::Publisher),
+            "owner" => Ok(Role::Owner),
+            _ => bail!("{} is not a valid role.", s),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Publisher => "publisher",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Role of the user, `None` if the user doesn't exist (anymore)
+pub async fn get_role(user_id: Uuid, pool: &PgPool) -> Result<Option<Role>> {
+    sqlx::query!(r#"SELECT role FROM users WHERE user_id = $1"#, user_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to retrieve the user's role")?
+        .map(|row| Role::parse(&row.role))
+        .transpose()
+}
+
+/// Lowest role allowed through a [`RequireRole`] guard
+pub trait RoleRequirement {
+    const MINIMUM: Role;
+}
+
+pub struct Editor;
+pub struct Publisher;
+pub struct Owner;
+
+impl RoleRequirement for Editor {
+    const MINIMUM: Role = Role::Editor;
+}
+
+impl RoleRequirement for Publisher {
+    const MINIMUM: Role = Role::Publisher;
+}
+
+impl RoleRequirement for Owner {
+    const MINIMUM: Role = Role::Owner;
+}
+
+/// Guard for handlers behind [`super::reject_anonymous_users`], rejects users below the required
+/// role with a 403. Take it as an argument, e.g. `_: RequireRole<Editor>`.
+pub struct RequireRole<R>(PhantomData<R>);
+
+impl<R: RoleRequirement> FromRequest for RequireRole<R> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = match req.extensions().get::<Role>() {
+            Some(role) if *role >= R::MINIMUM => Ok(RequireRole(PhantomData)),
+            Some(role) => Err(forbidden(anyhow!(
+                "The {} role is required, the user is a {}",
+                R::MINIMUM,
+                role
+            ))),
+            None => Err(forbidden(anyhow!("The user's role is unknown"))),
+        };
+        ready(result)
+    }
+}
+
+fn forbidden(e: anyhow::Error) -> actix_web::Error {
+    tracing::warn!("Rejecting request: {}", e);
+    actix_web::error::InternalError::from_response(e, HttpResponse::Forbidden().finish()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+
+    #[test]
+    fn roles_are_ordered_by_power() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Publisher);
+        assert!(Role::Publisher < Role::Owner);
+    }
+
+    #[test]
+    fn roles_round_trip_through_their_names() {
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.as_str()).unwrap(), role);
+        }
+        assert!(Role::parse("admin").is_err());
+    }
+}
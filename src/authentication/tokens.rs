@@ -0,0 +1,17 @@
+use rand::{Rng, distr::Alphanumeric};
+use sha2::{Digest, Sha256};
+
+/// Random case-sensitive alphanumeric token
+pub(super) fn generate_token(length: usize) -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Tokens are long and random, a fast unsalted hash is enough to keep a database leak from
+/// turning into account takeovers
+pub(super) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,353 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{Duration, Utc};
+use qrcode::{QrCode, render::svg};
+use sqlx::{PgPool, Postgres, Transaction};
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::tokens::{generate_token, hash_token};
+
+const TOTP_ISSUER: &str = "zero2prod";
+const TOTP_STEP_SECONDS: u64 = 30;
+/// Number of recovery codes handed out on enrollment
+pub const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+/// Wrong codes in a row before the second step is locked, whatever the number of logins
+const MAX_SECOND_FACTOR_ATTEMPTS: i32 = 5;
+/// How long the second step stays locked once too many wrong codes were entered
+pub const SECOND_FACTOR_LOCKOUT: Duration = Duration::minutes(15);
+
+/// Second factor of a user, as stored in `users`
+pub enum TotpState {
+    Disabled,
+    /// Enrollment started, the secret still has to be confirmed with a valid code
+    Pending {
+        secret: String,
+    },
+    Enabled,
+}
+
+#[instrument(name = "Get TOTP state", skip(pool))]
+pub async fn get_totp_state(pool: &PgPool, user_id: Uuid) -> Result<TotpState> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_enabled FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the TOTP state")?;
+
+    Ok(match (row.totp_enabled, row.totp_secret) {
+        (true, _) => TotpState::Enabled,
+        (false, Some(secret)) => TotpState::Pending { secret },
+        (false, None) => TotpState::Disabled,
+    })
+}
+
+/// Stores a fresh secret for the user, replacing any enrollment that wasn't completed
+#[instrument(name = "Start TOTP enrollment", skip(pool))]
+pub async fn start_totp_enrollment(pool: &PgPool, user_id: Uuid) -> Result<()> {
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $2, totp_last_used_step = NULL
+        WHERE user_id = $1 AND NOT totp_enabled
+        "#,
+        user_id,
+        secret,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the TOTP secret")?;
+    Ok(())
+}
+
+/// `otpauth://` URI understood by authenticator apps
+pub fn provisioning_uri(secret: &str, username: &str) -> Result<String> {
+    Ok(totp(secret, username)?.get_url())
+}
+
+/// The provisioning URI as an SVG QR code, to be embedded in a page
+pub fn qr_code_svg(provisioning_uri: &str) -> Result<String> {
+    Ok(QrCode::new(provisioning_uri.as_bytes())
+        .context("Failed to encode the provisioning URI as a QR code")?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+/// Completes enrollment if `code` matches the pending secret, returning fresh recovery codes
+#[instrument(name = "Enable TOTP", skip(pool, code))]
+pub async fn enable_totp(pool: &PgPool, user_id: Uuid, code: &str) -> Result<Option<Vec<String>>> {
+    let mut transaction = pool.begin().await?;
+    if !accept_totp_code(&mut transaction, user_id, code, false).await? {
+        return Ok(None);
+    }
+    sqlx::query!(
+        r#"UPDATE users SET totp_enabled = true WHERE user_id = $1"#,
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to enable TOTP")?;
+    let recovery_codes = replace_recovery_codes(&mut transaction, user_id).await?;
+    transaction.commit().await?;
+    Ok(Some(recovery_codes))
+}
+
+#[instrument(name = "Disable TOTP", skip(pool))]
+pub async fn disable_totp(pool: &PgPool, user_id: Uuid) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_enabled = false, totp_last_used_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to disable TOTP")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete recovery codes")?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Invalidates the current recovery codes and hands out new ones
+#[instrument(name = "Regenerate recovery codes", skip(pool))]
+pub async fn regenerate_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>> {
+    let mut transaction = pool.begin().await?;
+    let recovery_codes = replace_recovery_codes(&mut transaction, user_id).await?;
+    transaction.commit().await?;
+    Ok(recovery_codes)
+}
+
+/// Outcome of the second login step
+#[derive(Debug, PartialEq, Eq)]
+pub enum SecondFactorOutcome {
+    Accepted,
+    Rejected,
+    /// Too many wrong codes, no code is checked until the lockout expires
+    LockedOut,
+}
+
+/// Checks the second login step, `code` is either a current TOTP code or an unused recovery
+/// code. Accepted codes can't be used again. Failures are counted on the user, so that starting
+/// over with the password doesn't buy more guesses.
+#[instrument(name = "Verify second factor", skip(pool, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<SecondFactorOutcome> {
+    let code = code.trim();
+    let mut transaction = pool.begin().await?;
+    let lockout = sqlx::query!(
+        r#"
+        SELECT failed_second_factor_attempts, second_factor_locked_until
+        FROM users
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+        user_id,
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to retrieve the second factor lockout")?;
+    let now = Utc::now();
+    if lockout
+        .second_factor_locked_until
+        .is_some_and(|locked_until| locked_until > now)
+    {
+        return Ok(SecondFactorOutcome::LockedOut);
+    }
+
+    let accepted = if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        accept_totp_code(&mut transaction, user_id, code, true).await?
+    } else {
+        use_recovery_code(&mut transaction, user_id, code).await?
+    };
+    let (failed_attempts, locked_until, outcome) = if accepted {
+        (0, None, SecondFactorOutcome::Accepted)
+    } else if lockout.failed_second_factor_attempts + 1 >= MAX_SECOND_FACTOR_ATTEMPTS {
+        (
+            0,
+            Some(now + SECOND_FACTOR_LOCKOUT),
+            SecondFactorOutcome::LockedOut,
+        )
+    } else {
+        (
+            lockout.failed_second_factor_attempts + 1,
+            None,
+            SecondFactorOutcome::Rejected,
+        )
+    };
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET failed_second_factor_attempts = $2, second_factor_locked_until = $3
+        WHERE user_id = $1
+        "#,
+        user_id,
+        failed_attempts,
+        locked_until,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the second factor attempt")?;
+    transaction.commit().await?;
+    Ok(outcome)
+}
+
+/// Accepts codes from the previous, current and next time step to make up for clock drift, but
+/// never one from a step at or before the last accepted one
+async fn accept_totp_code(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    code: &str,
+    require_enabled: bool,
+) -> Result<bool> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, totp_secret, totp_enabled, totp_last_used_step
+        FROM users
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+        user_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to retrieve the TOTP secret")?;
+    let Some(secret) = row.totp_secret else {
+        return Ok(false);
+    };
+    if require_enabled && !row.totp_enabled {
+        return Ok(false);
+    }
+
+    let totp = totp(&secret, &row.username)?;
+    let current_step = unix_now()? / TOTP_STEP_SECONDS;
+    let matching_step = [current_step - 1, current_step, current_step + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS));
+    let Some(step) = matching_step else {
+        return Ok(false);
+    };
+    let step = i64::try_from(step).context("TOTP time step out of range")?;
+    if row.totp_last_used_step.is_some_and(|last| step <= last) {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"UPDATE users SET totp_last_used_step = $2 WHERE user_id = $1"#,
+        user_id,
+        step,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the used TOTP step")?;
+    Ok(true)
+}
+
+async fn use_recovery_code(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool> {
+    let n_used = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(&normalize_recovery_code(code)),
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to use recovery code")?
+    .rows_affected();
+    Ok(n_used > 0)
+}
+
+async fn replace_recovery_codes(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Vec<String>> {
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete recovery codes")?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let code = generate_token(RECOVERY_CODE_LENGTH).to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|c| hash_token(&normalize_recovery_code(c)))
+        .collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1, * FROM UNNEST($2::text[])
+        "#,
+        user_id,
+        &hashes,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store recovery codes")?;
+    Ok(codes)
+}
+
+/// Recovery codes are shown as `xxxxx-xxxxx`, accept them with or without the dash and in any case
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn totp(secret: &str, username: &str) -> Result<TOTP> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow!("Invalid TOTP secret: {:?}", e))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        // drift is handled by the caller, which also needs to know the matching step
+        0,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        username.to_string(),
+    )
+    .context("Failed to build the TOTP generator")
+}
+
+fn unix_now() -> Result<u64> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .context("System clock is before the UNIX epoch")?
+        .as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_recovery_code;
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        assert_eq!(normalize_recovery_code(" AbCde-fgh12 "), "abcdefgh12");
+    }
+}
@@ -0,0 +1,94 @@
+//! Command line equivalent of the admin import page, for files too big to upload comfortably
+//!
+//! ```text
+//! import_subscribers <file.csv> [--list <slug>] [--status <status>] [--report <rejected.csv>]
+//! ```
+//!
+//! The list defaults to the default list and the status to `confirmed`. The rejected rows are
+//! written to the report file if given, and can be downloaded from the admin imports page either
+//! way.
+
+use anyhow::{Context, Result, bail};
+use sqlx::postgres::PgPoolOptions;
+use zero2prod::{
+    configuration::get_configuration,
+    domain::{MailingList, SubscriberStatus},
+    subscriber_import::{import_subscribers, save_import},
+};
+
+const USAGE: &str = "Usage: import_subscribers <file.csv> [--list <slug>] [--status <status>] [--report <rejected.csv>]";
+
+struct Arguments {
+    file: String,
+    list: Option<String>,
+    status: SubscriberStatus,
+    report: Option<String>,
+}
+
+fn parse_arguments() -> Result<Arguments> {
+    let mut file = None;
+    let mut list = None;
+    let mut status = SubscriberStatus::Confirmed;
+    let mut report = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--list" => list = Some(value()?),
+            "--status" => status = SubscriberStatus::parse(&value()?)?,
+            "--report" => report = Some(value()?),
+            "-h" | "--help" => bail!(USAGE),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => bail!("Unexpected argument {arg}\n{USAGE}"),
+        }
+    }
+    Ok(Arguments {
+        file: file.context(USAGE)?,
+        list,
+        status,
+        report,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let arguments = parse_arguments()?;
+    let config = get_configuration().context("Failed to read configuration")?;
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(config.database.connection_options())
+        .await
+        .context("Failed to connect to the database")?;
+
+    let Some(list) = MailingList::find(&pool, arguments.list.as_deref()).await? else {
+        bail!(
+            "There is no list called {}",
+            arguments.list.unwrap_or_default()
+        );
+    };
+    let file = tokio::fs::File::open(&arguments.file)
+        .await
+        .with_context(|| format!("Failed to open {}", arguments.file))?;
+    let summary =
+        import_subscribers(&pool, &config.subscriptions, &list, arguments.status, file).await?;
+
+    let file_name = std::path::Path::new(&arguments.file)
+        .file_name()
+        .map_or(arguments.file.clone(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+    save_import(&pool, None, &file_name, &list, arguments.status, &summary).await?;
+    if let Some(report) = &arguments.report {
+        tokio::fs::write(report, summary.rejected_csv().await?)
+            .await
+            .with_context(|| format!("Failed to write {}", report))?;
+    }
+
+    println!(
+        "{} subscribers created, {} updated and {} rows rejected",
+        summary.n_created,
+        summary.n_updated,
+        summary.rejected.len()
+    );
+    Ok(())
+}
@@ -1,14 +1,43 @@
-use anyhow::{Result, bail};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::PgConnectOptions;
 
+use crate::domain::{AttributeDefinition, SubscriberEmail};
+use crate::email_client::{
+    EmailClient, EmailTransport, FileTransport, HttpTransport, LogTransport, RetryPolicy,
+    SmtpTransport,
+};
+
 #[derive(Deserialize, Debug)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub app: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub admin: Option<AdminSettings>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubscriptionSettings {
+    /// Reject signups from the bundled list of throwaway email providers
+    pub block_disposable_domains: bool,
+    /// Custom subscriber attributes accepted on signup and usable in segments
+    #[serde(default)]
+    pub attributes: Vec<AttributeDefinition>,
+}
+
+/// First admin user, created at startup if no user with that name exists
+#[derive(Deserialize, Debug)]
+pub struct AdminSettings {
+    pub username: String,
+    pub password: Secret<String>,
+    // receives password reset links
+    pub email: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -16,14 +45,100 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
+    pub base_url: String,
+    /// Signs session and flash message cookies
+    pub hmac_secret: Secret<String>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct EmailClientSettings {
-    pub base_url: String,
+    pub kind: EmailTransportKind,
     pub sender_email: String,
-    pub auth_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    pub retry: EmailRetrySettings,
+    // used by the `http` transport
+    pub base_url: String,
+    pub auth_token: Secret<String>,
+    // used by the `smtp` transport
+    pub smtp: SmtpSettings,
+    // used by the `file` transport
+    pub outbox_dir: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransportKind {
+    Http,
+    Smtp,
+    Log,
+    File,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    // leave empty to skip authentication
+    pub username: String,
+    pub password: Secret<String>,
+    pub starttls: bool,
+}
+
+impl EmailClientSettings {
+    pub fn client(&self) -> Result<EmailClient> {
+        let sender =
+            SubscriberEmail::parse(self.sender_email.clone()).context("Invalid sender email")?;
+        let timeout = Duration::from_millis(self.timeout_milliseconds);
+
+        let transport: Box<dyn EmailTransport> = match self.kind {
+            EmailTransportKind::Http => Box::new(
+                HttpTransport::new(self.base_url.clone(), self.auth_token.clone(), timeout)
+                    .context("Invalid email API base url")?,
+            ),
+            EmailTransportKind::Smtp => {
+                let credentials = (!self.smtp.username.is_empty())
+                    .then(|| (self.smtp.username.clone(), self.smtp.password.clone()));
+                Box::new(
+                    SmtpTransport::new(
+                        &self.smtp.host,
+                        self.smtp.port,
+                        credentials,
+                        self.smtp.starttls,
+                        timeout,
+                    )
+                    .context("Invalid SMTP relay configuration")?,
+                )
+            }
+            EmailTransportKind::Log => Box::new(LogTransport),
+            EmailTransportKind::File => Box::new(
+                FileTransport::new(self.outbox_dir.clone()).context("Invalid outbox directory")?,
+            ),
+        };
+
+        Ok(EmailClient::new(sender, transport, self.retry.policy()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmailRetrySettings {
+    pub max_attempts: u32,
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+    pub jitter: bool,
+    pub honor_retry_after: bool,
+}
+
+impl EmailRetrySettings {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            base_delay: Duration::from_millis(self.base_delay_milliseconds),
+            max_delay: Duration::from_millis(self.max_delay_milliseconds),
+            jitter: self.jitter,
+            honor_retry_after: self.honor_retry_after,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::domain::{SubscriberEmail, SubscriberEmailError};
+
+static DISPOSABLE_DOMAINS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    include_str!("disposable_email_domains.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+});
+
+/// Admin decision about a domain, stored in `email_domain_rules`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DomainRule {
+    Block,
+    /// Lets a domain from the disposable list through
+    Allow,
+}
+
+impl DomainRule {
+    pub const ALL: [DomainRule; 2] = [DomainRule::Block, DomainRule::Allow];
+
+    pub fn parse(s: &str) -> Result<DomainRule> {
+        match s {
+            "block" => Ok(DomainRule::Block),
+            "allow" => Ok(DomainRule::Allow),
+            _ => bail!("{} is not a valid domain rule.", s),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DomainRule::Block => "block",
+            DomainRule::Allow => "allow",
+        }
+    }
+}
+
+impl std::fmt::Display for DomainRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which email domains may subscribe. Admin rules win over the bundled disposable list, and the
+/// most specific rule wins among them, so `allow mail.example.com` carves an exception out of
+/// `block example.com`.
+pub struct DomainPolicy {
+    rules: HashMap<String, DomainRule>,
+    block_disposable: bool,
+}
+
+impl DomainPolicy {
+    pub fn new(rules: HashMap<String, DomainRule>, block_disposable: bool) -> DomainPolicy {
+        DomainPolicy {
+            rules,
+            block_disposable,
+        }
+    }
+
+    pub async fn load(pool: &PgPool, block_disposable: bool) -> Result<DomainPolicy> {
+        let rows = sqlx::query!(r#"SELECT domain, rule FROM email_domain_rules"#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to retrieve email domain rules")?;
+        let rules = rows
+            .into_iter()
+            .map(|r| Ok((r.domain, DomainRule::parse(&r.rule)?)))
+            .collect::<Result<_>>()?;
+        Ok(DomainPolicy::new(rules, block_disposable))
+    }
+
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), SubscriberEmailError> {
+        let domain = email.domain();
+        let rule = parent_domains(domain).find_map(|d| self.rules.get(d));
+        match rule {
+            Some(DomainRule::Allow) => Ok(()),
+            Some(DomainRule::Block) => Err(SubscriberEmailError::BlockedDomain(domain.to_string())),
+            None if self.block_disposable
+                && parent_domains(domain).any(|d| DISPOSABLE_DOMAINS.contains(d)) =>
+            {
+                Err(SubscriberEmailError::DisposableDomain(domain.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// `a.b.c`, `b.c` and `c`, most specific first
+fn parent_domains(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |d| {
+        d.split_once('.').map(|(_, parent)| parent)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{DomainPolicy, DomainRule};
+    use crate::domain::{SubscriberEmail, SubscriberEmailError};
+
+    fn check(policy: &DomainPolicy, email: &str) -> Result<(), SubscriberEmailError> {
+        policy.check(&SubscriberEmail::parse(email.to_string()).unwrap())
+    }
+
+    #[test]
+    fn disposable_domains_and_their_subdomains_are_rejected() {
+        let policy = DomainPolicy::new(HashMap::new(), true);
+        assert_eq!(
+            check(&policy, "ursula@Mailinator.com"),
+            Err(SubscriberEmailError::DisposableDomain(
+                "mailinator.com".to_string()
+            ))
+        );
+        assert!(check(&policy, "ursula@eu.mailinator.com").is_err());
+        assert!(check(&policy, "ursula@example.com").is_ok());
+    }
+
+    #[test]
+    fn disposable_domains_can_be_let_through() {
+        let allowed = DomainPolicy::new(
+            HashMap::from([("mailinator.com".to_string(), DomainRule::Allow)]),
+            true,
+        );
+        let not_blocking = DomainPolicy::new(HashMap::new(), false);
+        assert!(check(&allowed, "ursula@mailinator.com").is_ok());
+        assert!(check(&not_blocking, "ursula@mailinator.com").is_ok());
+    }
+
+    #[test]
+    fn the_most_specific_rule_wins() {
+        let policy = DomainPolicy::new(
+            HashMap::from([
+                ("example.com".to_string(), DomainRule::Block),
+                ("staff.example.com".to_string(), DomainRule::Allow),
+            ]),
+            true,
+        );
+        assert_eq!(
+            check(&policy, "ursula@mail.example.com"),
+            Err(SubscriberEmailError::BlockedDomain(
+                "mail.example.com".to_string()
+            ))
+        );
+        assert!(check(&policy, "ursula@staff.example.com").is_ok());
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result, bail};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The list signups and issues go to when none is given, created with the `lists` table
+pub const DEFAULT_LIST_SLUG: &str = "newsletter";
+
+const MAX_SLUG_LENGTH: usize = 64;
+
+/// URL-safe identifier of a list, e.g. `weekly-digest`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    /// Lowercase ASCII letters, digits and inner hyphens
+    pub fn parse(s: &str) -> Result<ListSlug> {
+        let s = s.trim();
+        if s.is_empty() || s.len() > MAX_SLUG_LENGTH {
+            bail!(
+                "A list slug must be between 1 and {} characters long.",
+                MAX_SLUG_LENGTH
+            );
+        }
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            || s.starts_with('-')
+            || s.ends_with('-')
+        {
+            bail!(
+                "{} is not a valid list slug, use lowercase letters, digits and hyphens.",
+                s
+            );
+        }
+        Ok(ListSlug(s.to_string()))
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ListSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A row of `lists`, i.e. one of the newsletters run from this deployment
+#[derive(Debug, Clone)]
+pub struct MailingList {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+impl MailingList {
+    /// Looks up the list by slug, the default list when `slug` is `None`
+    pub async fn find(pool: &PgPool, slug: Option<&str>) -> Result<Option<MailingList>> {
+        sqlx::query_as!(
+            MailingList,
+            r#"SELECT list_id, slug, name FROM lists WHERE slug = $1"#,
+            slug.unwrap_or(DEFAULT_LIST_SLUG),
+        )
+        .fetch_optional(pool)
+        .await
+        .context("Failed to retrieve the list")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ListSlug;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn lowercase_slugs_with_inner_hyphens_are_valid() {
+        assert_ok!(ListSlug::parse("weekly-digest"));
+        assert_ok!(ListSlug::parse("product-updates-2"));
+    }
+
+    #[test]
+    fn uppercase_letters_spaces_and_outer_hyphens_are_rejected() {
+        assert_err!(ListSlug::parse("Weekly"));
+        assert_err!(ListSlug::parse("weekly digest"));
+        assert_err!(ListSlug::parse("-weekly"));
+        assert_err!(ListSlug::parse("weekly-"));
+    }
+
+    #[test]
+    fn empty_and_overlong_slugs_are_rejected() {
+        assert_err!(ListSlug::parse(" "));
+        assert_err!(ListSlug::parse(&"a".repeat(65)));
+    }
+}
@@ -1,7 +1,23 @@
+mod email_domain_policy;
+mod mailing_list;
 mod new_subscriber;
+mod segment;
+mod subscriber_attributes;
 mod subscriber_email;
+mod subscriber_filter;
 mod subscriber_name;
+mod subscriber_status;
+mod tag_name;
 
-pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
-pub use subscriber_name::SubscriberName;
+pub use email_domain_policy::{DomainPolicy, DomainRule};
+pub use mailing_list::{DEFAULT_LIST_SLUG, ListSlug, MailingList};
+pub use new_subscriber::{FieldError, InvalidNewSubscriber, NewSubscriber};
+pub use segment::{Segment, SegmentError};
+pub use subscriber_attributes::{
+    AttributeDefinition, AttributeKind, AttributeValue, SubscriberAttributes,
+};
+pub use subscriber_email::{SubscriberEmail, SubscriberEmailError};
+pub use subscriber_filter::{SubscriberFilter, bind_values};
+pub use subscriber_name::{SubscriberName, SubscriberNameError};
+pub use subscriber_status::SubscriberStatus;
+pub use tag_name::{TagName, TagNameError};
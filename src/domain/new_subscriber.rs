@@ -1,28 +1,95 @@
-use anyhow::Result;
+use serde::Serialize;
 
-use crate::{
-    domain::{SubscriberEmail, subscriber_name::SubscriberName},
-    routes::FormData,
+use crate::domain::{
+    AttributeDefinition, SubscriberAttributes, SubscriberEmail, SubscriberEmailError,
+    subscriber_name::SubscriberName,
 };
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub attributes: SubscriberAttributes,
 }
 
+/// Why a single field of a new subscriber was rejected
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// `name`, `email` or `attributes.<name>`
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn email(e: SubscriberEmailError) -> FieldError {
+        FieldError {
+            field: "email".to_string(),
+            code: e.code(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Every invalid field of a new subscriber, not just the first one
+#[derive(Debug)]
+pub struct InvalidNewSubscriber(pub Vec<FieldError>);
+
+impl std::fmt::Display for InvalidNewSubscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.0.iter().map(|e| e.message.as_str()).collect();
+        f.write_str(&messages.join(" "))
+    }
+}
+
+impl std::error::Error for InvalidNewSubscriber {}
+
 impl NewSubscriber {
-    pub fn new(name: String, email: String) -> Result<NewSubscriber> {
-        Ok(NewSubscriber {
-            name: SubscriberName::parse(name)?,
-            email: SubscriberEmail::parse(email)?,
-        })
+    pub fn new(name: String, email: String) -> Result<NewSubscriber, InvalidNewSubscriber> {
+        NewSubscriber::with_attributes(name, email, &[], [])
+    }
+
+    /// Also validates custom attributes against the deployment's definitions
+    pub fn with_attributes(
+        name: String,
+        email: String,
+        definitions: &[AttributeDefinition],
+        attributes: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<NewSubscriber, InvalidNewSubscriber> {
+        match (
+            SubscriberName::parse(name).map_err(|e| FieldError {
+                field: "name".to_string(),
+                code: e.code(),
+                message: e.to_string(),
+            }),
+            SubscriberEmail::parse(email).map_err(FieldError::email),
+            SubscriberAttributes::parse(definitions, attributes),
+        ) {
+            (Ok(name), Ok(email), Ok(attributes)) => Ok(NewSubscriber {
+                name,
+                email,
+                attributes,
+            }),
+            (name, email, attributes) => Err(InvalidNewSubscriber(
+                [name.err(), email.err()]
+                    .into_iter()
+                    .flatten()
+                    .chain(attributes.err().into_iter().flatten())
+                    .collect(),
+            )),
+        }
     }
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = anyhow::Error;
+#[cfg(test)]
+mod tests {
+    use super::NewSubscriber;
 
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        NewSubscriber::new(value.name, value.email)
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let Err(e) = NewSubscriber::new("".to_string(), "not-an-email".to_string()) else {
+            panic!("An empty name and an invalid email were accepted");
+        };
+        let fields: Vec<&str> = e.0.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["name", "email"]);
     }
 }
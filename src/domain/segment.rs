@@ -0,0 +1,550 @@
+use crate::domain::{AttributeDefinition, AttributeKind, AttributeValue};
+
+/// Deepest nesting of parentheses and `not` accepted, parsing and compiling are recursive
+const MAX_SEGMENT_DEPTH: usize = 64;
+/// Most conditions accepted in a segment, long `and`/`or` chains nest just as deeply
+const MAX_SEGMENT_CONDITIONS: usize = 256;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("Invalid segment at position {position}: {message}")]
+pub struct SegmentError {
+    /// Byte offset into the definition
+    pub position: usize,
+    pub message: String,
+}
+
+/// Subset of subscribers an issue is sent to, e.g.
+/// `country = "AU" and signed_up_after 2026-01-01`.
+///
+/// Conditions compare `email`, `name`, `subscribed_at` or a custom attribute with a literal
+/// (`=`, `!=`, `<`, `<=`, `>`, `>=`; text and boolean fields only support equality), and
+/// `signed_up_after`/`signed_up_before` take a date. They combine with `and`, `or`, `not` and
+/// parentheses. Text is quoted, numbers, dates (`YYYY-MM-DD`) and `true`/`false` are not.
+/// Segments are limited in size and nesting, see [`MAX_SEGMENT_CONDITIONS`] and
+/// [`MAX_SEGMENT_DEPTH`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Field, Op, AttributeValue),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Email,
+    Name,
+    SubscribedAt,
+    Attribute(String, AttributeKind),
+}
+
+impl Field {
+    fn kind(&self) -> AttributeKind {
+        match self {
+            Field::Email | Field::Name => AttributeKind::Text,
+            Field::SubscribedAt => AttributeKind::Date,
+            Field::Attribute(_, kind) => *kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Text(String),
+    /// A number or a date, told apart once the field type is known
+    Literal(String),
+    Op(Op),
+    LeftParen,
+    RightParen,
+}
+
+impl Segment {
+    pub fn parse(
+        definition: &str,
+        attributes: &[AttributeDefinition],
+    ) -> Result<Segment, SegmentError> {
+        let mut parser = Parser {
+            tokens: tokenize(definition)?,
+            next: 0,
+            end: definition.len(),
+            depth: 0,
+            n_conditions: 0,
+            attributes,
+        };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.next) {
+            Some((position, token)) => Err(SegmentError {
+                position: *position,
+                message: format!("Unexpected {}.", describe(token)),
+            }),
+            None => Ok(Segment(expr)),
+        }
+    }
+
+    /// Compiles the segment to a SQL condition on the `subscriptions` row aliased `s`. Every
+    /// value, attribute names included, is a placeholder numbered from `first_param`; the values
+    /// to bind are returned in placeholder order.
+    pub fn to_sql(&self, first_param: usize) -> (String, Vec<AttributeValue>) {
+        let mut writer = SqlWriter {
+            params: Vec::new(),
+            first_param,
+        };
+        let sql = writer.write(&self.0);
+        (sql, writer.params)
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(s) => format!("`{}`", s),
+        Token::Text(s) => format!("\"{}\"", s),
+        Token::Literal(s) => format!("`{}`", s),
+        Token::Op(op) => format!("`{}`", op.as_sql()),
+        Token::LeftParen => "`(`".to_string(),
+        Token::RightParen => "`)`".to_string(),
+    }
+}
+
+fn tokenize(definition: &str) -> Result<Vec<(usize, Token)>, SegmentError> {
+    let mut tokens = Vec::new();
+    let mut chars = definition.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '=' => Token::Op(Op::Eq),
+            '!' if chars.next_if(|(_, c)| *c == '=').is_some() => Token::Op(Op::Ne),
+            '<' => match chars.next_if(|(_, c)| *c == '=') {
+                Some(_) => Token::Op(Op::Le),
+                None => Token::Op(Op::Lt),
+            },
+            '>' => match chars.next_if(|(_, c)| *c == '=') {
+                Some(_) => Token::Op(Op::Ge),
+                None => Token::Op(Op::Gt),
+            },
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    let next = match chars.next() {
+                        Some((_, '"')) => break,
+                        // a backslash escapes the next character, even a quote
+                        Some((_, '\\')) => chars.next(),
+                        next => next,
+                    };
+                    match next {
+                        Some((_, c)) => text.push(c),
+                        None => {
+                            return Err(SegmentError {
+                                position,
+                                message: "Unterminated text.".to_string(),
+                            });
+                        }
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut literal = c.to_string();
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '-' || *c == '.')
+                {
+                    literal.push(c);
+                }
+                Token::Literal(literal)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => {
+                return Err(SegmentError {
+                    position,
+                    message: format!("Unexpected character `{}`.", c),
+                });
+            }
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent, `not` binds tighter than `and`, which binds tighter than `or`
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+    /// Parentheses and `not`s around the current position
+    depth: usize,
+    n_conditions: usize,
+    attributes: &'a [AttributeDefinition],
+}
+
+impl Parser<'_> {
+    fn parse_or(&mut self) -> Result<Expr, SegmentError> {
+        let mut expr = self.parse_and()?;
+        while self.next_is_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, SegmentError> {
+        let mut expr = self.parse_not()?;
+        while self.next_is_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, SegmentError> {
+        if !self.next_is_keyword("not") {
+            return self.parse_primary();
+        }
+        self.enter(self.tokens[self.next - 1].0)?;
+        let expr = self.parse_not()?;
+        self.depth -= 1;
+        Ok(Expr::Not(Box::new(expr)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SegmentError> {
+        let (position, token) = self.advance("a condition")?;
+        let name = match token {
+            Token::LeftParen => {
+                self.enter(position)?;
+                let expr = self.parse_or()?;
+                self.depth -= 1;
+                return match self.advance("`)`")? {
+                    (_, Token::RightParen) => Ok(expr),
+                    (position, token) => Err(unexpected(position, &token, "`)`")),
+                };
+            }
+            Token::Ident(name) => name,
+            token => return Err(unexpected(position, &token, "a condition")),
+        };
+        self.n_conditions += 1;
+        if self.n_conditions > MAX_SEGMENT_CONDITIONS {
+            return Err(SegmentError {
+                position,
+                message: format!(
+                    "A segment can't have more than {} conditions.",
+                    MAX_SEGMENT_CONDITIONS
+                ),
+            });
+        }
+
+        let (field, op) = match name.as_str() {
+            "signed_up_after" => (Field::SubscribedAt, Op::Gt),
+            "signed_up_before" => (Field::SubscribedAt, Op::Lt),
+            _ => {
+                let field = self.field(position, name)?;
+                let op = match self.advance("a comparison")? {
+                    (_, Token::Op(op)) => op,
+                    (position, token) => return Err(unexpected(position, &token, "a comparison")),
+                };
+                if !matches!(op, Op::Eq | Op::Ne)
+                    && matches!(field.kind(), AttributeKind::Text | AttributeKind::Boolean)
+                {
+                    return Err(SegmentError {
+                        position,
+                        message: format!(
+                            "{} fields can only be compared with `=` and `!=`.",
+                            field.kind()
+                        ),
+                    });
+                }
+                (field, op)
+            }
+        };
+        let value = self.value(field.kind())?;
+        Ok(Expr::Condition(field, op, value))
+    }
+
+    fn enter(&mut self, position: usize) -> Result<(), SegmentError> {
+        self.depth += 1;
+        if self.depth > MAX_SEGMENT_DEPTH {
+            return Err(SegmentError {
+                position,
+                message: format!(
+                    "A segment can't be nested more than {} levels deep.",
+                    MAX_SEGMENT_DEPTH
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn field(&self, position: usize, name: String) -> Result<Field, SegmentError> {
+        match name.as_str() {
+            "email" => Ok(Field::Email),
+            "name" => Ok(Field::Name),
+            "subscribed_at" => Ok(Field::SubscribedAt),
+            _ => match self.attributes.iter().find(|a| a.name == name) {
+                Some(attribute) => Ok(Field::Attribute(name, attribute.kind)),
+                None => Err(SegmentError {
+                    position,
+                    message: format!("There is no field called {}.", name),
+                }),
+            },
+        }
+    }
+
+    fn value(&mut self, kind: AttributeKind) -> Result<AttributeValue, SegmentError> {
+        let expected = format!("a {} value", kind);
+        let (position, token) = self.advance(&expected)?;
+        let raw = match (kind, &token) {
+            (AttributeKind::Text, Token::Text(s)) => return Ok(AttributeValue::Text(s.clone())),
+            (AttributeKind::Number, Token::Literal(s)) => s,
+            (AttributeKind::Date, Token::Literal(s) | Token::Text(s)) => s,
+            (AttributeKind::Boolean, Token::Ident(s)) if s == "true" || s == "false" => s,
+            _ => return Err(unexpected(position, &token, &expected)),
+        };
+        AttributeValue::parse(kind, raw).map_err(|message| SegmentError { position, message })
+    }
+
+    fn next_is_keyword(&mut self, keyword: &str) -> bool {
+        let matches = matches!(
+            self.tokens.get(self.next),
+            Some((_, Token::Ident(s))) if s.eq_ignore_ascii_case(keyword)
+        );
+        if matches {
+            self.next += 1;
+        }
+        matches
+    }
+
+    fn advance(&mut self, expected: &str) -> Result<(usize, Token), SegmentError> {
+        let token = self.tokens.get(self.next).cloned().ok_or(SegmentError {
+            position: self.end,
+            message: format!("Expected {}, found the end of the segment.", expected),
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+}
+
+fn unexpected(position: usize, token: &Token, expected: &str) -> SegmentError {
+    SegmentError {
+        position,
+        message: format!("Expected {}, found {}.", expected, describe(token)),
+    }
+}
+
+struct SqlWriter {
+    params: Vec<AttributeValue>,
+    first_param: usize,
+}
+
+impl SqlWriter {
+    fn bind(&mut self, value: AttributeValue) -> String {
+        self.params.push(value);
+        format!("${}", self.first_param + self.params.len() - 1)
+    }
+
+    fn write(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Or(a, b) => format!("({} OR {})", self.write(a), self.write(b)),
+            Expr::And(a, b) => format!("({} AND {})", self.write(a), self.write(b)),
+            Expr::Not(a) => format!("(NOT {})", self.write(a)),
+            // missing attributes compare as false rather than NULL, so `not` includes them
+            Expr::Condition(field, op, value) => {
+                let op = op.as_sql();
+                let column = match field {
+                    Field::Email => "lower(s.email)".to_string(),
+                    Field::Name => "s.name".to_string(),
+                    Field::SubscribedAt => "(s.subscribed_at AT TIME ZONE 'UTC')::date".to_string(),
+                    Field::Attribute(name, kind) => {
+                        let name = self.bind(AttributeValue::Text(name.clone()));
+                        let value = self.bind(value.clone());
+                        // a stored value of another type, e.g. after the definition changed,
+                        // never matches instead of failing the cast
+                        let condition = match kind {
+                            AttributeKind::Text => {
+                                format!("(s.attributes ->> {name}) {op} {value}")
+                            }
+                            AttributeKind::Number => format!(
+                                "CASE WHEN jsonb_typeof(s.attributes -> {name}) = 'number' \
+                                THEN (s.attributes ->> {name})::float8 {op} {value} END"
+                            ),
+                            AttributeKind::Date => format!(
+                                "CASE WHEN (s.attributes ->> {name}) ~ '^[0-9]{{4}}-[0-9]{{2}}-[0-9]{{2}}$' \
+                                THEN (s.attributes ->> {name})::date {op} {value} END"
+                            ),
+                            AttributeKind::Boolean => format!(
+                                "CASE WHEN jsonb_typeof(s.attributes -> {name}) = 'boolean' \
+                                THEN (s.attributes ->> {name})::boolean {op} {value} END"
+                            ),
+                        };
+                        return format!("COALESCE({condition}, false)");
+                    }
+                };
+                let value = match field {
+                    Field::Email => format!("lower({})", self.bind(value.clone())),
+                    _ => self.bind(value.clone()),
+                };
+                format!("COALESCE({column} {op} {value}, false)")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::Segment;
+    use crate::domain::{AttributeDefinition, AttributeKind, AttributeValue};
+
+    fn attributes() -> Vec<AttributeDefinition> {
+        [
+            ("country", AttributeKind::Text),
+            ("age", AttributeKind::Number),
+            ("vip", AttributeKind::Boolean),
+        ]
+        .into_iter()
+        .map(|(name, kind)| AttributeDefinition {
+            name: name.to_string(),
+            kind,
+        })
+        .collect()
+    }
+
+    fn error_message(definition: &str) -> String {
+        Segment::parse(definition, &attributes())
+            .unwrap_err()
+            .message
+    }
+
+    #[test]
+    fn segments_compile_to_parameterized_sql() {
+        let segment = Segment::parse(
+            r#"country = "AU" and signed_up_after 2026-01-01"#,
+            &attributes(),
+        )
+        .unwrap();
+
+        let (sql, params) = segment.to_sql(2);
+
+        assert_eq!(
+            sql,
+            "(COALESCE((s.attributes ->> $2) = $3, false) AND \
+            COALESCE((s.subscribed_at AT TIME ZONE 'UTC')::date > $4, false))"
+        );
+        assert_eq!(
+            params,
+            [
+                AttributeValue::Text("country".to_string()),
+                AttributeValue::Text("AU".to_string()),
+                AttributeValue::Date(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let (sql, _) = Segment::parse(
+            r#"vip = true or age >= 18 and not name = "x""#,
+            &attributes(),
+        )
+        .unwrap()
+        .to_sql(1);
+        assert!(sql.starts_with("(COALESCE(CASE WHEN jsonb_typeof(s.attributes -> $1)"));
+        assert!(sql.contains(" OR (COALESCE(CASE"));
+        assert!(sql.ends_with("AND (NOT COALESCE(s.name = $5, false))))"));
+    }
+
+    #[test]
+    fn values_must_match_the_field_type() {
+        assert_eq!(
+            error_message("age > \"old\""),
+            "Expected a number value, found \"old\"."
+        );
+        assert_eq!(
+            error_message("signed_up_after 2026-13-01"),
+            "2026-13-01 is not a date, use YYYY-MM-DD."
+        );
+        assert_eq!(
+            error_message("country > \"AU\""),
+            "text fields can only be compared with `=` and `!=`."
+        );
+    }
+
+    #[test]
+    fn malformed_segments_are_rejected_with_a_position() {
+        let e = Segment::parse("shoe_size = 9", &attributes()).unwrap_err();
+        assert_eq!(e.position, 0);
+        assert_eq!(e.message, "There is no field called shoe_size.");
+        let e = Segment::parse("(age > 1", &attributes()).unwrap_err();
+        assert_eq!(e.position, 8);
+        assert!(Segment::parse("", &attributes()).is_err());
+        assert!(Segment::parse("age > 1 age", &attributes()).is_err());
+        assert!(Segment::parse("name = \"unterminated", &attributes()).is_err());
+    }
+
+    #[test]
+    fn a_trailing_backslash_does_not_terminate_a_text() {
+        let e = Segment::parse(r#"name = "abc\"#, &attributes()).unwrap_err();
+        assert_eq!(e.position, 7);
+        assert_eq!(e.message, "Unterminated text.");
+        let (_, params) = Segment::parse(r#"name = "a \"b\" \\""#, &attributes())
+            .unwrap()
+            .to_sql(1);
+        assert_eq!(params, [AttributeValue::Text(r#"a "b" \"#.to_string())]);
+    }
+
+    #[test]
+    fn deeply_nested_segments_are_rejected() {
+        let e = Segment::parse(&"(".repeat(100_000), &attributes()).unwrap_err();
+        assert_eq!(e.position, 64);
+        assert_eq!(
+            e.message,
+            "A segment can't be nested more than 64 levels deep."
+        );
+        let e = Segment::parse(&"not ".repeat(100_000), &attributes()).unwrap_err();
+        assert_eq!(e.position, 256);
+        let nested = format!("{}age > 1{}", "(".repeat(64), ")".repeat(64));
+        assert!(Segment::parse(&nested, &attributes()).is_ok());
+    }
+
+    #[test]
+    fn segments_with_too_many_conditions_are_rejected() {
+        let chain = vec!["age > 1"; 257].join(" or ");
+        assert_eq!(
+            error_message(&chain),
+            "A segment can't have more than 256 conditions."
+        );
+        assert!(Segment::parse(&vec!["age > 1"; 256].join(" or "), &attributes()).is_ok());
+    }
+}
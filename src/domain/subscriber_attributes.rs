@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::domain::FieldError;
+
+/// Type of a custom attribute, decides which values and segment comparisons are accepted
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeKind {
+    Text,
+    Number,
+    /// `YYYY-MM-DD`
+    Date,
+    Boolean,
+}
+
+impl AttributeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttributeKind::Text => "text",
+            AttributeKind::Number => "number",
+            AttributeKind::Date => "date",
+            AttributeKind::Boolean => "boolean",
+        }
+    }
+}
+
+impl std::fmt::Display for AttributeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A custom attribute subscribers can have, configured per deployment
+#[derive(Deserialize, Debug, Clone)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+    Boolean(bool),
+}
+
+impl AttributeValue {
+    pub fn parse(kind: AttributeKind, s: &str) -> Result<AttributeValue, String> {
+        let s = s.trim();
+        match kind {
+            AttributeKind::Text => Ok(AttributeValue::Text(s.to_string())),
+            AttributeKind::Number => s
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(AttributeValue::Number)
+                .ok_or_else(|| format!("{} is not a number.", s)),
+            AttributeKind::Date => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(AttributeValue::Date)
+                .map_err(|_| format!("{} is not a date, use YYYY-MM-DD.", s)),
+            AttributeKind::Boolean => match s {
+                "true" | "on" | "yes" | "1" => Ok(AttributeValue::Boolean(true)),
+                "false" | "off" | "no" | "0" => Ok(AttributeValue::Boolean(false)),
+                _ => Err(format!("{} is not a boolean.", s)),
+            },
+        }
+    }
+
+    /// As stored in `subscriptions.attributes`, dates are kept as `YYYY-MM-DD` strings
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            AttributeValue::Text(s) => s.clone().into(),
+            AttributeValue::Number(n) => (*n).into(),
+            AttributeValue::Date(d) => d.format("%Y-%m-%d").to_string().into(),
+            AttributeValue::Boolean(b) => (*b).into(),
+        }
+    }
+}
+
+/// Validated custom attributes of a subscriber
+#[derive(Debug, Clone, Default)]
+pub struct SubscriberAttributes(BTreeMap<String, AttributeValue>);
+
+impl SubscriberAttributes {
+    /// Parses raw values against the definitions, empty values are treated as missing. Every
+    /// invalid or unknown attribute is reported.
+    pub fn parse(
+        definitions: &[AttributeDefinition],
+        raw: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<SubscriberAttributes, Vec<FieldError>> {
+        let mut attributes = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (name, value) in raw {
+            if value.trim().is_empty() {
+                continue;
+            }
+            let field = format!("attributes.{}", name);
+            let Some(definition) = definitions.iter().find(|d| d.name == name) else {
+                errors.push(FieldError {
+                    message: format!("There is no attribute called {}.", name),
+                    field,
+                    code: "attribute_unknown",
+                });
+                continue;
+            };
+            match AttributeValue::parse(definition.kind, &value) {
+                Ok(value) => {
+                    attributes.insert(name, value);
+                }
+                Err(message) => errors.push(FieldError {
+                    field,
+                    code: "attribute_invalid",
+                    message,
+                }),
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(SubscriberAttributes(attributes)),
+            false => Err(errors),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_json()))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeDefinition, AttributeKind, SubscriberAttributes};
+
+    fn definitions() -> Vec<AttributeDefinition> {
+        [
+            ("country", AttributeKind::Text),
+            ("age", AttributeKind::Number),
+            ("birthday", AttributeKind::Date),
+            ("vip", AttributeKind::Boolean),
+        ]
+        .into_iter()
+        .map(|(name, kind)| AttributeDefinition {
+            name: name.to_string(),
+            kind,
+        })
+        .collect()
+    }
+
+    fn raw(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn values_are_stored_with_their_type() {
+        let attributes = SubscriberAttributes::parse(
+            &definitions(),
+            raw(&[
+                ("country", "AU"),
+                ("age", "42"),
+                ("birthday", "1990-05-01"),
+                ("vip", "true"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            attributes.to_json(),
+            serde_json::json!({
+                "country": "AU",
+                "age": 42.0,
+                "birthday": "1990-05-01",
+                "vip": true,
+            })
+        );
+    }
+
+    #[test]
+    fn empty_values_are_skipped() {
+        let attributes = SubscriberAttributes::parse(&definitions(), raw(&[("age", " ")])).unwrap();
+        assert!(attributes.is_empty());
+    }
+
+    #[test]
+    fn every_invalid_or_unknown_attribute_is_reported() {
+        let errors = SubscriberAttributes::parse(
+            &definitions(),
+            raw(&[
+                ("age", "old"),
+                ("birthday", "01/05/1990"),
+                ("shoe_size", "9"),
+            ]),
+        )
+        .unwrap_err();
+        let codes: Vec<(&str, &str)> = errors.iter().map(|e| (e.field.as_str(), e.code)).collect();
+        assert_eq!(
+            codes,
+            [
+                ("attributes.age", "attribute_invalid"),
+                ("attributes.birthday", "attribute_invalid"),
+                ("attributes.shoe_size", "attribute_unknown"),
+            ]
+        );
+    }
+}
@@ -1,28 +1,82 @@
-use anyhow::Result;
 use validator::ValidateEmail;
 
+/// A valid email address. The local part is kept as typed, the domain is lowercased and
+/// converted to punycode, surrounding whitespace is dropped.
 #[derive(Debug)]
-pub struct SubscriberEmail(String);
+pub struct SubscriberEmail {
+    original: String,
+    normalized: String,
+}
+
+/// Why an email address was rejected, the messages are safe to show to the subscriber
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SubscriberEmailError {
+    #[error("{0} is not a valid email address.")]
+    Invalid(String),
+    #[error("Addresses at {0} are disposable, please use a permanent email address.")]
+    DisposableDomain(String),
+    #[error("Addresses at {0} are not accepted.")]
+    BlockedDomain(String),
+}
+
+impl SubscriberEmailError {
+    /// Stable identifier for API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubscriberEmailError::Invalid(_) => "email_invalid",
+            SubscriberEmailError::DisposableDomain(_) => "email_domain_disposable",
+            SubscriberEmailError::BlockedDomain(_) => "email_domain_blocked",
+        }
+    }
+}
 
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<SubscriberEmail> {
-        match s.validate_email() {
-            true => Ok(SubscriberEmail(s)),
-            false => anyhow::bail!("{} is not a valid subscriber email.", s),
+    pub fn parse(s: String) -> Result<SubscriberEmail, SubscriberEmailError> {
+        let original = s.trim();
+        let normalized = original
+            .rsplit_once('@')
+            .and_then(|(local, domain)| {
+                let domain = idna::domain_to_ascii(domain).ok()?;
+                Some(format!("{}@{}", local, domain))
+            })
+            .filter(|normalized| normalized.validate_email());
+        match normalized {
+            Some(normalized) => Ok(SubscriberEmail {
+                original: original.to_string(),
+                normalized,
+            }),
+            None => Err(SubscriberEmailError::Invalid(s)),
         }
     }
+
+    /// The address as entered, minus surrounding whitespace
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// The address as stored and used for sending
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+
+    /// The normalized domain
+    pub fn domain(&self) -> &str {
+        self.normalized
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.normalized
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::SubscriberEmail;
-    use claims::assert_err;
+    use claims::{assert_err, assert_ok};
     use fake::{Fake, faker::internet::en::SafeEmail};
     use quickcheck_macros::quickcheck;
     use rand::{SeedableRng, rngs::StdRng};
@@ -60,4 +114,24 @@ mod tests {
     fn valid_emails_are_parsed_successfully(valid_email: ValidEmailFixture) -> bool {
         SubscriberEmail::parse(valid_email.0).is_ok()
     }
+
+    #[test]
+    fn the_domain_is_lowercased_and_whitespace_trimmed() {
+        let email = SubscriberEmail::parse("  Ursula@Example.COM ".to_string()).unwrap();
+        assert_eq!(email.original(), "Ursula@Example.COM");
+        assert_eq!(email.normalized(), "Ursula@example.com");
+    }
+
+    #[test]
+    fn international_domains_are_converted_to_punycode() {
+        let email = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(email.normalized(), "ursula@xn--bcher-kva.example");
+        assert_eq!(email.domain(), "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn invalid_domains_are_rejected() {
+        assert_err!(SubscriberEmail::parse("ursula@exa mple.com".to_string()));
+        assert_ok!(SubscriberEmail::parse("ursula@example.com".to_string()));
+    }
 }
@@ -0,0 +1,121 @@
+use sqlx::{Postgres, postgres::PgArguments, query::Query};
+
+use crate::domain::{AttributeValue, Segment, SubscriberStatus, TagName};
+
+/// Which subscribers a bulk operation or an issue applies to, every given condition has to hold
+#[derive(Debug, Clone, Default)]
+pub struct SubscriberFilter {
+    pub status: Option<SubscriberStatus>,
+    /// Slug of a list the subscriber is pending or confirmed on
+    pub list: Option<String>,
+    pub segment: Option<Segment>,
+    /// Subscribers with at least one of these tags
+    pub include_tags: Vec<TagName>,
+    /// Subscribers with none of these tags
+    pub exclude_tags: Vec<TagName>,
+}
+
+impl SubscriberFilter {
+    /// Compiles to a SQL condition on the `subscriptions` row aliased `s`, with the same
+    /// placeholder scheme as [`Segment::to_sql`]. An empty filter matches everyone.
+    pub fn to_sql(&self, first_param: usize) -> (String, Vec<AttributeValue>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        let bind = |params: &mut Vec<AttributeValue>, value: &str| {
+            params.push(AttributeValue::Text(value.to_string()));
+            format!("${}", first_param + params.len() - 1)
+        };
+
+        if let Some(status) = self.status {
+            conditions.push(format!("s.status = {}", bind(&mut params, status.as_str())));
+        }
+        if let Some(list) = &self.list {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM list_memberships m JOIN lists l ON l.list_id = m.list_id \
+                WHERE m.subscriber_id = s.id AND m.status <> 'unsubscribed' AND l.slug = {})",
+                bind(&mut params, list)
+            ));
+        }
+        for (tags, negate) in [(&self.include_tags, ""), (&self.exclude_tags, "NOT ")] {
+            if tags.is_empty() {
+                continue;
+            }
+            let placeholders: Vec<String> = tags
+                .iter()
+                .map(|tag| bind(&mut params, tag.as_ref()))
+                .collect();
+            conditions.push(format!(
+                "{negate}EXISTS (SELECT 1 FROM subscriber_tags st JOIN tags t ON t.tag_id = st.tag_id \
+                WHERE st.subscriber_id = s.id AND t.name IN ({}))",
+                placeholders.join(", ")
+            ));
+        }
+        if let Some(segment) = &self.segment {
+            let (condition, segment_params) = segment.to_sql(first_param + params.len());
+            conditions.push(condition);
+            params.extend(segment_params);
+        }
+
+        match conditions.is_empty() {
+            true => ("true".to_string(), params),
+            false => (conditions.join(" AND "), params),
+        }
+    }
+}
+
+/// Binds the values returned by [`SubscriberFilter::to_sql`] or [`Segment::to_sql`], in order
+pub fn bind_values(
+    mut query: Query<'_, Postgres, PgArguments>,
+    values: Vec<AttributeValue>,
+) -> Query<'_, Postgres, PgArguments> {
+    for value in values {
+        query = match value {
+            AttributeValue::Text(s) => query.bind(s),
+            AttributeValue::Number(n) => query.bind(n),
+            AttributeValue::Date(d) => query.bind(d),
+            AttributeValue::Boolean(b) => query.bind(b),
+        };
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberFilter;
+    use crate::domain::{AttributeValue, Segment, SubscriberStatus, TagName};
+
+    #[test]
+    fn an_empty_filter_matches_everyone() {
+        assert_eq!(
+            SubscriberFilter::default().to_sql(1),
+            ("true".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn conditions_are_combined_with_consecutive_placeholders() {
+        let filter = SubscriberFilter {
+            status: Some(SubscriberStatus::Confirmed),
+            exclude_tags: vec![
+                TagName::parse("churned").unwrap(),
+                TagName::parse("test").unwrap(),
+            ],
+            segment: Some(Segment::parse(r#"name = "x""#, &[]).unwrap()),
+            ..Default::default()
+        };
+
+        let (sql, params) = filter.to_sql(2);
+
+        assert!(sql.starts_with("s.status = $2 AND NOT EXISTS ("));
+        assert!(sql.contains("t.name IN ($3, $4))"));
+        assert!(sql.ends_with("AND COALESCE(s.name = $5, false)"));
+        let params: Vec<String> = params
+            .into_iter()
+            .map(|p| match p {
+                AttributeValue::Text(s) => s,
+                p => panic!("Unexpected parameter {:?}", p),
+            })
+            .collect();
+        assert_eq!(params, ["confirmed", "churned", "test", "x"]);
+    }
+}
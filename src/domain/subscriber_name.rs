@@ -1,23 +1,45 @@
-use anyhow::{Result, bail};
 use unicode_segmentation::UnicodeSegmentation;
 
 pub const MAX_NAME_LENGTH: usize = 256;
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
 
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
+/// Why a subscriber name was rejected, the messages are safe to show to the subscriber
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SubscriberNameError {
+    #[error("The name can't be empty.")]
+    Empty,
+    #[error("The name can't be longer than {MAX_NAME_LENGTH} characters.")]
+    TooLong,
+    #[error("The name can't contain '{0}'.")]
+    ForbiddenCharacter(char),
+}
+
+impl SubscriberNameError {
+    /// Stable identifier for API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubscriberNameError::Empty => "name_empty",
+            SubscriberNameError::TooLong => "name_too_long",
+            SubscriberNameError::ForbiddenCharacter(_) => "name_forbidden_character",
+        }
+    }
+}
+
 impl SubscriberName {
-    pub fn parse(s: String) -> Result<SubscriberName> {
-        // true -> invalid name
-        // false -> valid name
-        match s.trim().is_empty()
-            || s.graphemes(true).count() > MAX_NAME_LENGTH
-            || s.chars()
-                .any(|c| ['/', '(', ')', '"', '<', '>', '\\', '{', '}'].contains(&c))
-        {
-            true => bail!("{} is not a valid subscriber name.", s),
-            false => Ok(SubscriberName(s.trim().to_string())),
+    pub fn parse(s: String) -> Result<SubscriberName, SubscriberNameError> {
+        if s.trim().is_empty() {
+            return Err(SubscriberNameError::Empty);
+        }
+        if s.graphemes(true).count() > MAX_NAME_LENGTH {
+            return Err(SubscriberNameError::TooLong);
+        }
+        if let Some(c) = s.chars().find(|c| FORBIDDEN_CHARACTERS.contains(c)) {
+            return Err(SubscriberNameError::ForbiddenCharacter(c));
         }
+        Ok(SubscriberName(s.trim().to_string()))
     }
 }
 
@@ -29,7 +51,7 @@ impl AsRef<str> for SubscriberName {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::subscriber_name::{MAX_NAME_LENGTH, SubscriberName};
+    use crate::domain::subscriber_name::{MAX_NAME_LENGTH, SubscriberName, SubscriberNameError};
     use claims::{assert_err, assert_ok};
     #[test]
     fn a_256_grapheme_long_name_is_valid() {
@@ -40,13 +62,19 @@ mod tests {
     #[test]
     fn a_name_longer_than_256_graphemes_is_rejected() {
         let name = "a".repeat(MAX_NAME_LENGTH + 1);
-        assert_err!(SubscriberName::parse(name));
+        assert_eq!(
+            SubscriberName::parse(name).unwrap_err(),
+            SubscriberNameError::TooLong
+        );
     }
 
     #[test]
     fn whitespace_only_names_are_rejected() {
         let name = " ".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_eq!(
+            SubscriberName::parse(name).unwrap_err(),
+            SubscriberNameError::Empty
+        );
     }
 
     #[test]
@@ -57,9 +85,12 @@ mod tests {
 
     #[test]
     fn names_containing_an_invalid_character_are_rejected() {
-        for name in &['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
-            let name = name.to_string();
-            assert_err!(SubscriberName::parse(name));
+        for c in ['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
+            let name = format!("Ursula {c}");
+            assert_eq!(
+                SubscriberName::parse(name).unwrap_err(),
+                SubscriberNameError::ForbiddenCharacter(c)
+            );
         }
     }
 
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Values stored in `subscriptions.status`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl SubscriberStatus {
+    pub const ALL: [SubscriberStatus; 3] = [
+        SubscriberStatus::PendingConfirmation,
+        SubscriberStatus::Confirmed,
+        SubscriberStatus::Unsubscribed,
+    ];
+
+    pub fn parse(s: &str) -> Result<SubscriberStatus> {
+        SubscriberStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .with_context(|| format!("{} is not a valid subscriber status.", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriberStatus::PendingConfirmation => "pending_confirmation",
+            SubscriberStatus::Confirmed => "confirmed",
+            SubscriberStatus::Unsubscribed => "unsubscribed",
+        }
+    }
+}
+
+impl std::fmt::Display for SubscriberStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
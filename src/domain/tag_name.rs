@@ -0,0 +1,89 @@
+const MAX_TAG_LENGTH: usize = 64;
+
+/// Label put on subscribers, e.g. `conference-2026`. Tags are case-insensitive and stored in
+/// lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagName(String);
+
+/// Why a tag was rejected, the messages are safe to show to the user
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TagNameError {
+    #[error("A tag can't be empty.")]
+    Empty,
+    #[error("A tag can't be longer than {MAX_TAG_LENGTH} characters.")]
+    TooLong,
+    #[error("{0} is not a valid tag, use letters, digits, hyphens and underscores.")]
+    InvalidCharacters(String),
+}
+
+impl TagNameError {
+    /// Stable identifier for API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            TagNameError::Empty => "tag_empty",
+            TagNameError::TooLong => "tag_too_long",
+            TagNameError::InvalidCharacters(_) => "tag_invalid_characters",
+        }
+    }
+}
+
+impl TagName {
+    /// Lowercase ASCII letters, digits, hyphens and underscores, starting with a letter or digit
+    pub fn parse(s: &str) -> Result<TagName, TagNameError> {
+        let s = s.trim().to_ascii_lowercase();
+        if s.is_empty() {
+            return Err(TagNameError::Empty);
+        }
+        if s.len() > MAX_TAG_LENGTH {
+            return Err(TagNameError::TooLong);
+        }
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+            || !s.starts_with(|c: char| c.is_ascii_alphanumeric())
+        {
+            return Err(TagNameError::InvalidCharacters(s));
+        }
+        Ok(TagName(s))
+    }
+
+    /// Parses every tag, failing on the first invalid one
+    pub fn parse_all<S: AsRef<str>>(tags: &[S]) -> Result<Vec<TagName>, TagNameError> {
+        tags.iter().map(|t| TagName::parse(t.as_ref())).collect()
+    }
+}
+
+impl AsRef<str> for TagName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for TagName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TagName, TagNameError};
+    use claims::{assert_err, assert_err_eq, assert_ok_eq};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        assert_ok_eq!(
+            TagName::parse(" Conference-2026 ").map(|t| t.to_string()),
+            "conference-2026"
+        );
+    }
+
+    #[test]
+    fn spaces_punctuation_and_leading_hyphens_are_rejected() {
+        assert_err!(TagName::parse("paying customer"));
+        assert_err!(TagName::parse("beta!"));
+        assert_err!(TagName::parse("-beta"));
+        assert_err_eq!(TagName::parse(" "), TagNameError::Empty);
+        assert_err_eq!(TagName::parse(&"a".repeat(65)), TagNameError::TooLong);
+    }
+}
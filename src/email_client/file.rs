@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{EmailMessage, EmailTransport, SendEmailError, smtp::build_message};
+
+/// Writes every outgoing email into a local outbox directory instead of delivering it: the full
+/// message as an `.eml` file, next to a JSON sidecar with the same name that is easier to inspect
+/// from code (see [`latest_message_for`]).
+pub struct FileTransport {
+    outbox_dir: PathBuf,
+}
+
+/// Contents of the JSON sidecar written next to each `.eml` file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    pub headers: HashMap<String, String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl FileTransport {
+    pub fn new(outbox_dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&outbox_dir).context(format!(
+            "Failed to create outbox directory {}",
+            outbox_dir.display()
+        ))?;
+        Ok(Self { outbox_dir })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), SendEmailError> {
+        let eml = build_message(message)?.formatted();
+        let sidecar = OutboxMessage {
+            from: message.from.as_ref().to_string(),
+            to: message.to.as_ref().to_string(),
+            subject: message.subject.to_string(),
+            html: message.html.to_string(),
+            text: message.text.to_string(),
+            headers: message
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            sent_at: Utc::now(),
+        };
+        let sidecar = serde_json::to_vec_pretty(&sidecar)
+            .map_err(|e| SendEmailError::Permanent(e.to_string()))?;
+
+        // sortable by name, the uuid avoids clashes between messages written at the same time
+        let name = format!(
+            "{}_{}",
+            Utc::now().format("%Y%m%dT%H%M%S%6fZ"),
+            Uuid::new_v4()
+        );
+        let base_path = self.outbox_dir.join(name);
+        // the sidecar goes last, once it exists the message is complete
+        tokio::fs::write(base_path.with_extension("eml"), eml)
+            .await
+            .map_err(|e| SendEmailError::Misconfigured(e.into()))?;
+        tokio::fs::write(base_path.with_extension("json"), sidecar)
+            .await
+            .map_err(|e| SendEmailError::Misconfigured(e.into()))?;
+
+        Ok(())
+    }
+}
+
+/// Reads the most recent message written to the outbox for the given recipient, if any
+pub fn latest_message_for(outbox_dir: &Path, recipient: &str) -> Result<Option<OutboxMessage>> {
+    let mut sidecars = std::fs::read_dir(outbox_dir)
+        .context(format!(
+            "Failed to read outbox directory {}",
+            outbox_dir.display()
+        ))?
+        .map(|entry| entry.map(|e| e.path()))
+        .filter(|path| {
+            path.as_ref()
+                .map(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .unwrap_or(true)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // file names start with the time they were written at
+    sidecars.sort();
+
+    for path in sidecars.into_iter().rev() {
+        let message: OutboxMessage = serde_json::from_slice(&std::fs::read(&path)?)
+            .context(format!("Invalid outbox sidecar {}", path.display()))?;
+        if message.to == recipient {
+            return Ok(Some(message));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use claims::assert_none;
+    use uuid::Uuid;
+
+    use super::{FileTransport, latest_message_for};
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailMessage, EmailTransport, SendEmailError};
+
+    #[tokio::test]
+    async fn messages_are_written_to_the_outbox() {
+        let outbox_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let transport = FileTransport::new(outbox_dir.clone()).unwrap();
+        let from = SubscriberEmail::parse("newsletter@example.com".to_string()).unwrap();
+        let headers = HashMap::new();
+
+        for (recipient, subject) in [
+            ("ursula@example.com", "First"),
+            ("ursula@example.com", "Second"),
+            ("octavia@example.com", "Third"),
+        ] {
+            let to = SubscriberEmail::parse(recipient.to_string()).unwrap();
+            let message = EmailMessage {
+                from: &from,
+                to: &to,
+                subject,
+                html: "<p>Hello there</p>",
+                text: "Hello there",
+                headers: &headers,
+            };
+            transport.send(&message).await.unwrap();
+        }
+
+        let latest = latest_message_for(&outbox_dir, "ursula@example.com")
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.subject, "Second");
+        assert_eq!(latest.text, "Hello there");
+        assert_none!(latest_message_for(&outbox_dir, "nobody@example.com").unwrap());
+
+        let n_emls = std::fs::read_dir(&outbox_dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "eml")
+            .count();
+        assert_eq!(n_emls, 3);
+
+        std::fs::remove_dir_all(outbox_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn outbox_write_failures_are_not_retried() {
+        let outbox_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let transport = FileTransport::new(outbox_dir.clone()).unwrap();
+        std::fs::remove_dir_all(&outbox_dir).unwrap();
+        let email = SubscriberEmail::parse("ursula@example.com".to_string()).unwrap();
+        let headers = HashMap::new();
+        let message = EmailMessage {
+            from: &email,
+            to: &email,
+            subject: "Subject",
+            html: "<p>Hello there</p>",
+            text: "Hello there",
+            headers: &headers,
+        };
+
+        let outcome = transport.send(&message).await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Misconfigured(_))));
+        assert!(!outcome.unwrap_err().is_retryable());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Response, StatusCode, Url, header::RETRY_AFTER};
+use secrecy::{ExposeSecret, Secret};
+
+use super::{EmailMessage, EmailTransport, SendEmailError};
+
+/// Delivers emails through a Resend-style `POST /email` JSON API
+pub struct HttpTransport {
+    url: Url,
+    http_client: Client,
+    auth_token: Secret<String>,
+    timeout: Duration,
+}
+
+#[derive(serde::Serialize)]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html: &'a str,
+    text: &'a str,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: &'a HashMap<&'static str, String>,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: String, auth_token: Secret<String>, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            url: Url::parse(base_url.as_str())?.join("/email")?,
+            http_client: Client::new(),
+            auth_token,
+            timeout,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for HttpTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), SendEmailError> {
+        let body = SendEmailRequest {
+            from: message.from.as_ref(),
+            to: message.to.as_ref(),
+            subject: message.subject,
+            html: message.html,
+            text: message.text,
+            headers: message.headers,
+        };
+
+        let response = self
+            .http_client
+            .post(self.url.clone())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.auth_token.expose_secret()),
+            )
+            .json(&body)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(classify_response(&response)),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendEmailError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            SendEmailError::Timeout(e.into())
+        } else {
+            SendEmailError::Network(e.into())
+        }
+    }
+}
+
+fn classify_response(response: &Response) -> SendEmailError {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => SendEmailError::RateLimited {
+            retry_after: response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after),
+        },
+        status if status.is_server_error() => SendEmailError::Transient(status.to_string()),
+        status => SendEmailError::Permanent(status.to_string()),
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    DateTime::parse_from_rfc2822(value.trim()).ok().map(|date| {
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::parse_retry_after;
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
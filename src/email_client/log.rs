@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+use tracing::info;
+
+use super::{EmailMessage, EmailTransport, SendEmailError};
+
+/// Doesn't deliver anything, only logs the emails it's handed. Handy for local development or
+/// deployments that shouldn't send emails at all.
+pub struct LogTransport;
+
+#[async_trait]
+impl EmailTransport for LogTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), SendEmailError> {
+        info!(
+            from = message.from.as_ref(),
+            to = message.to.as_ref(),
+            subject = message.subject,
+            "Not delivering email, the log transport is configured:\n{}",
+            message.text
+        );
+        Ok(())
+    }
+}
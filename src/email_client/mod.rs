@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::Rng;
+use tracing::warn;
+
+use crate::domain::SubscriberEmail;
+
+mod file;
+mod http;
+mod log;
+mod smtp;
+
+pub use file::{FileTransport, OutboxMessage, latest_message_for};
+pub use http::HttpTransport;
+pub use log::LogTransport;
+pub use smtp::SmtpTransport;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A single outgoing email, as handed to an [`EmailTransport`]
+pub struct EmailMessage<'a> {
+    pub from: &'a SubscriberEmail,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html: &'a str,
+    pub text: &'a str,
+    /// Extra headers on top of the ones every transport sets itself (from, to, subject, ...)
+    pub headers: &'a HashMap<&'static str, String>,
+}
+
+/// Something able to hand an email over for delivery, e.g. an HTTP email API or an SMTP relay.
+/// Transports make a single attempt, retries are handled by [`EmailClient`].
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), SendEmailError>;
+}
+
+pub struct EmailClient {
+    sender: SubscriberEmail,
+    transport: Box<dyn EmailTransport>,
+    retry_policy: RetryPolicy,
+}
+
+/// How failed deliveries are retried, only failures for which [`SendEmailError::is_retryable`]
+/// holds are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every following one
+    pub base_delay: Duration,
+    /// Upper bound for any single delay, including the ones requested through `Retry-After`
+    pub max_delay: Duration,
+    /// Randomise delays so that clients failing together don't retry together
+    pub jitter: bool,
+    /// Wait for as long as the provider asks for in `Retry-After` when rate limited
+    pub honor_retry_after: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    #[error("Request to the email provider timed out")]
+    Timeout(#[source] BoxError),
+    #[error("Failed to reach the email provider")]
+    Network(#[source] BoxError),
+    #[error("Email provider rate limited the request")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Email provider failed: {0}")]
+    Transient(String),
+    #[error("Email provider rejected the message: {0}")]
+    Permanent(String),
+    /// Retrying won't help until the configuration is fixed, e.g. an unwritable outbox directory
+    #[error("The email transport is misconfigured")]
+    Misconfigured(#[source] BoxError),
+}
+
+impl SendEmailError {
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            SendEmailError::Permanent(_) | SendEmailError::Misconfigured(_)
+        )
+    }
+}
+
+impl EmailClient {
+    pub fn new(
+        sender: SubscriberEmail,
+        transport: Box<dyn EmailTransport>,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            sender,
+            transport,
+            retry_policy,
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
+        self.send(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            HashMap::new(),
+        )
+        .await
+    }
+
+    /// Sends an email to a list member, advertising one-click unsubscribe (RFC 8058) so that
+    /// mail clients can show their native unsubscribe button
+    pub async fn send_list_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: &str,
+    ) -> Result<(), SendEmailError> {
+        let headers = HashMap::from([
+            ("List-Unsubscribe", format!("<{}>", unsubscribe_link)),
+            (
+                "List-Unsubscribe-Post",
+                "List-Unsubscribe=One-Click".to_string(),
+            ),
+        ]);
+
+        self.send(recipient, subject, html_content, text_content, headers)
+            .await
+    }
+
+    async fn send(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: HashMap<&'static str, String>,
+    ) -> Result<(), SendEmailError> {
+        let message = EmailMessage {
+            from: &self.sender,
+            to: recipient,
+            subject,
+            html: html_content,
+            text: text_content,
+            headers: &headers,
+        };
+
+        let mut attempt = 1;
+        loop {
+            match self.transport.send(&message).await {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_retryable() && attempt < self.retry_policy.max_attempts => {
+                    let delay = self.retry_policy.delay(attempt, &e);
+                    warn!(
+                        "Attempt {} at sending an email failed, retrying in {:?}: {}",
+                        attempt, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait for after the given (1-based) failed attempt
+    fn delay(&self, attempt: u32, error: &SendEmailError) -> Duration {
+        if let SendEmailError::RateLimited {
+            retry_after: Some(retry_after),
+        } = error
+            && self.honor_retry_after
+        {
+            return (*retry_after).min(self.max_delay);
+        }
+
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        match self.jitter {
+            // "equal jitter": keep half of the delay, randomise the other half
+            true => delay / 2 + rand::rng().random_range(Duration::ZERO..=delay / 2),
+            false => delay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use claims::{assert_err, assert_ok};
+    use fake::{
+        Fake, Faker,
+        faker::{
+            internet::en::SafeEmail,
+            lorem::en::{Paragraph, Sentence},
+        },
+    };
+    use secrecy::Secret;
+    use wiremock::{
+        Match, Mock, MockServer, ResponseTemplate,
+        matchers::{any, header, header_exists, method, path},
+    };
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{EmailClient, HttpTransport, RetryPolicy, SendEmailError},
+    };
+
+    struct SendEmailBodyMatcher;
+
+    impl Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("from").is_some()
+                    && body.get("to").is_some()
+                    && body.get("subject").is_some()
+                    && body.get("html").is_some()
+                    && body.get("text").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    struct ListUnsubscribeHeadersMatcher(String);
+
+    impl Match for ListUnsubscribeHeadersMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body["headers"]["List-Unsubscribe"] == format!("<{}>", self.0)
+                    && body["headers"]["List-Unsubscribe-Post"] == "List-Unsubscribe=One-Click"
+            } else {
+                false
+            }
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    const MAX_ATTEMPTS: u64 = 3;
+
+    fn email_client(base_url: String) -> EmailClient {
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let transport = HttpTransport::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        EmailClient::new(
+            sender,
+            Box::new(transport),
+            RetryPolicy {
+                max_attempts: MAX_ATTEMPTS as u32,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_secs(5),
+                jitter: false,
+                honor_retry_after: true,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_expected_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("Authorization"))
+            .and(SendEmailBodyMatcher)
+            .and(header("Content-type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = email();
+        let subject = subject();
+        let content = content();
+
+        let _ = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_list_email_includes_list_unsubscribe_headers() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let unsubscribe_link =
+            "https://example.com/subscriptions/unsubscribe?unsubscribe_token=abc";
+
+        Mock::given(SendEmailBodyMatcher)
+            .and(ListUnsubscribeHeadersMatcher(unsubscribe_link.to_string()))
+            .and(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = email();
+        let subject = subject();
+        let content = content();
+
+        let outcome = email_client
+            .send_list_email(
+                &subscriber_email,
+                &subject,
+                &content,
+                &content,
+                unsubscribe_link,
+            )
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_server_returns_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = email();
+        let subject = subject();
+        let content = content();
+
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_server_keeps_returning_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(MAX_ATTEMPTS)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = email();
+        let subject = subject();
+        let content = content();
+
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(MAX_ATTEMPTS)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = email();
+        let subject = subject();
+        let content = content();
+
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_retry_client_errors() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Permanent(_))));
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_after_a_transient_failure() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_honors_retry_after_when_rate_limited() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let start = Instant::now();
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{
+        Mailbox, MultiPart,
+        header::{HeaderName, HeaderValue},
+    },
+    transport::smtp::authentication::Credentials,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use super::{EmailMessage, EmailTransport, SendEmailError};
+
+/// Delivers emails through an SMTP relay, optionally upgrading the connection with STARTTLS and
+/// authenticating
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        starttls: bool,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut builder = match starttls {
+            true => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            // plaintext, only meant for relays on a trusted network
+            false => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        }
+        .port(port)
+        .timeout(Some(timeout));
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_string(),
+            ));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), SendEmailError> {
+        let message = build_message(message)?;
+        self.mailer.send(message).await?;
+        Ok(())
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for SendEmailError {
+    fn from(e: lettre::transport::smtp::Error) -> Self {
+        if e.is_timeout() {
+            SendEmailError::Timeout(e.into())
+        } else if e.is_permanent() {
+            SendEmailError::Permanent(e.to_string())
+        } else if e.is_transient() {
+            SendEmailError::Transient(e.to_string())
+        } else {
+            // connection, TLS or protocol level failure
+            SendEmailError::Network(e.into())
+        }
+    }
+}
+
+pub(super) fn build_message(message: &EmailMessage<'_>) -> Result<Message, SendEmailError> {
+    let mailbox = |address: &str| {
+        address
+            .parse::<Mailbox>()
+            .map_err(|e| SendEmailError::Permanent(format!("Invalid address {}: {}", address, e)))
+    };
+
+    let mut builder = Message::builder()
+        .from(mailbox(message.from.as_ref())?)
+        .to(mailbox(message.to.as_ref())?)
+        .subject(message.subject);
+    for (name, value) in message.headers {
+        builder = builder.raw_header(HeaderValue::new(
+            HeaderName::new_from_ascii_str(name),
+            value.clone(),
+        ));
+    }
+
+    builder
+        .multipart(MultiPart::alternative_plain_html(
+            message.text.to_string(),
+            message.html.to_string(),
+        ))
+        .map_err(|e| SendEmailError::Permanent(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::EmailMessage;
+
+    use super::build_message;
+
+    #[test]
+    fn messages_carry_both_bodies_and_extra_headers() {
+        let from = SubscriberEmail::parse("newsletter@example.com".to_string()).unwrap();
+        let to = SubscriberEmail::parse("ursula@example.com".to_string()).unwrap();
+        let headers = HashMap::from([(
+            "List-Unsubscribe",
+            "<https://example.com/unsubscribe>".to_string(),
+        )]);
+        let message = EmailMessage {
+            from: &from,
+            to: &to,
+            subject: "Hello",
+            html: "<p>Hello there</p>",
+            text: "Hello there",
+            headers: &headers,
+        };
+
+        let formatted = String::from_utf8(build_message(&message).unwrap().formatted()).unwrap();
+
+        assert!(formatted.contains("List-Unsubscribe: <https://example.com/unsubscribe>"));
+        assert!(formatted.contains("Content-Type: text/plain"));
+        assert!(formatted.contains("Content-Type: text/html"));
+        assert!(formatted.contains("Subject: Hello"));
+    }
+}
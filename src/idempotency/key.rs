@@ -0,0 +1,59 @@
+use actix_web::HttpRequest;
+use anyhow::{Result, bail};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+pub const MAX_KEY_LENGTH: usize = 50;
+
+#[derive(Debug)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    pub fn parse(s: String) -> Result<IdempotencyKey> {
+        if s.is_empty() {
+            bail!("The idempotency key cannot be empty");
+        }
+        if s.len() >= MAX_KEY_LENGTH {
+            bail!(
+                "The idempotency key must be shorter than {} characters",
+                MAX_KEY_LENGTH
+            );
+        }
+        Ok(IdempotencyKey(s))
+    }
+
+    /// Reads the optional `Idempotency-Key` header, failing if it's present but invalid
+    pub fn from_request(request: &HttpRequest) -> Result<Option<IdempotencyKey>> {
+        request
+            .headers()
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .map(|value| IdempotencyKey::parse(value.to_str()?.to_string()))
+            .transpose()
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::idempotency::key::{IdempotencyKey, MAX_KEY_LENGTH};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn empty_key_is_rejected() {
+        assert_err!(IdempotencyKey::parse("".to_string()));
+    }
+
+    #[test]
+    fn a_key_that_is_too_long_is_rejected() {
+        assert_err!(IdempotencyKey::parse("a".repeat(MAX_KEY_LENGTH)));
+    }
+
+    #[test]
+    fn a_uuid_is_a_valid_key() {
+        assert_ok!(IdempotencyKey::parse(uuid::Uuid::new_v4().to_string()));
+    }
+}
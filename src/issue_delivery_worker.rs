@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{Span, error, field::display, instrument, warn};
+use uuid::Uuid;
+
+use crate::{domain::SubscriberEmail, email_client::EmailClient, routes::unsubscribe_link};
+
+/// Number of attempts at delivering an issue to a subscriber before the task is dropped
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+
+const EMPTY_QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    n_retries: i32,
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<()> {
+    loop {
+        match try_execute_task(&pool, email_client, base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(EMPTY_QUEUE_POLL_INTERVAL).await,
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Err(e) => {
+                error!("Failed to execute delivery task: {:?}", e);
+                tokio::time::sleep(ERROR_BACKOFF).await;
+            }
+        }
+    }
+}
+
+/// Picks up a single due task from the delivery queue and tries to deliver it. Rows are locked
+/// with `SKIP LOCKED`, so any number of workers can share the queue.
+#[instrument(
+    skip_all,
+    fields(newsletter_issue_id = tracing::field::Empty, subscriber_id = tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<ExecutionOutcome> {
+    let Some((mut transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(task.newsletter_issue_id))
+        .record("subscriber_id", display(task.subscriber_id));
+
+    // the membership of the list the issue was published to decides, not the overall status
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT s.email, s.unsubscribe_token, m.status, l.slug AS list_slug
+        FROM newsletter_issues i
+        JOIN lists l ON l.list_id = i.list_id
+        JOIN list_memberships m ON m.list_id = i.list_id AND m.subscriber_id = $2
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let recipient = match subscriber {
+        Some(s) if s.status == "confirmed" => match SubscriberEmail::parse(s.email) {
+            Ok(email) => Some((email, s.unsubscribe_token, s.list_slug)),
+            Err(e) => {
+                warn!(
+                    "Skipping a confirmed subscriber, their stored contact details are invalid: {}",
+                    e
+                );
+                None
+            }
+        },
+        // the subscriber left the list after the issue was published
+        _ => None,
+    };
+
+    if let Some((email, unsubscribe_token, list_slug)) = recipient {
+        let issue = get_issue(&mut transaction, task.newsletter_issue_id).await?;
+        let unsubscribe_link = unsubscribe_link(base_url, &unsubscribe_token, &list_slug);
+        if let Err(e) = email_client
+            .send_list_email(
+                &email,
+                &issue.title,
+                &issue.html_content,
+                &issue.text_content,
+                &unsubscribe_link,
+            )
+            .await
+        {
+            // the email client already retried transient failures, this is the slower, durable
+            // layer on top of it
+            if e.is_retryable() && task.n_retries + 1 < MAX_DELIVERY_ATTEMPTS {
+                warn!(
+                    "Failed to deliver issue to a confirmed subscriber, will retry: {:?}",
+                    e
+                );
+                reschedule_task(transaction, &task).await?;
+                return Ok(ExecutionOutcome::TaskCompleted);
+            }
+            error!(
+                "Failed to deliver issue to a confirmed subscriber, giving up after {} attempts: {:?}",
+                task.n_retries + 1,
+                e
+            );
+        }
+    }
+
+    delete_task(transaction, &task).await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(Transaction<'static, Postgres>, Task)>> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT newsletter_issue_id, subscriber_id, n_retries
+        FROM issue_delivery_queue
+        WHERE execute_after <= now()
+        ORDER BY execute_after
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    Ok(task.map(|task| (transaction, task)))
+}
+
+async fn delete_task(mut transaction: Transaction<'static, Postgres>, task: &Task) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Pushes the task back with an exponential delay (1, 2, 4, ... minutes)
+async fn reschedule_task(
+    mut transaction: Transaction<'static, Postgres>,
+    task: &Task,
+) -> Result<()> {
+    let delay = chrono::Duration::minutes(1 << task.n_retries);
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET n_retries = n_retries + 1, execute_after = $3
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        Utc::now() + delay,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+async fn get_issue(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<NewsletterIssue> {
+    sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to retrieve newsletter issue")
+}
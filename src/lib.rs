@@ -1,5 +1,12 @@
+use actix_session::SessionMiddleware;
+use actix_session::config::CookieContentSecurity;
+use actix_web::cookie::Key;
+use actix_web::middleware::from_fn;
 use actix_web::{App, HttpServer, dev::Server, web};
+use actix_web_flash_messages::FlashMessagesFramework;
+use actix_web_flash_messages::storage::CookieMessageStore;
 use anyhow::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use tracing::subscriber::set_global_default;
@@ -12,18 +19,43 @@ use tracing_subscriber::{EnvFilter, Registry};
 use uuid::Uuid;
 
 use std::net::TcpListener;
-use std::sync::LazyLock;
-use std::time::Duration;
+use std::sync::{Arc, LazyLock};
 
-use crate::configuration::{Settings, get_configuration};
-use crate::domain::SubscriberEmail;
+use crate::authentication::{ensure_user, reject_anonymous_users};
+use crate::configuration::{Settings, SubscriptionSettings, get_configuration};
 use crate::email_client::EmailClient;
-use crate::routes::{health_check, subscribe};
+use crate::issue_delivery_worker::run_worker_until_stopped;
+use crate::problem_details::invalid_request;
+use crate::routes::api::{
+    add_subscriber_tags, bulk_tag_subscribers, create_subscriber, get_stats, remove_subscriber_tag,
+};
+use crate::routes::{
+    add_api_key, add_email_domain_rule, add_list, add_user, admin_dashboard, bulk_tag_action,
+    change_password, change_password_form, change_user_role, confirm, confirm_subscriber,
+    delete_email_domain_rule, delete_subscriber, delete_tag_action, delete_user,
+    disable_two_factor, download_rejected_rows, enable_two_factor, enroll_two_factor,
+    export_subscribers, forgot_password_form, health_check, list_api_keys, list_email_domain_rules,
+    list_imports, list_lists, list_subscribers, list_tags, list_users, log_out, login, login_form,
+    publish_newsletter, regenerate_two_factor_recovery_codes, request_password_reset,
+    reset_password, reset_password_form, revoke_api_key_action, subscribe, tag_subscriber,
+    two_factor, two_factor_form, two_factor_settings, unsubscribe, unsubscribe_form,
+    unsubscribe_subscriber, untag_subscriber, upload_import, view_subscriber,
+};
+use crate::session::PostgresSessionStore;
 
+pub mod authentication;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod problem_details;
 pub mod routes;
+pub mod session;
+pub mod subscriber_export;
+pub mod subscriber_import;
+pub mod tags;
+pub mod utils;
 
 // TODO: maybe move this to a more specfic tests file
 pub static TEST_TRACING: std::sync::LazyLock<()> = std::sync::LazyLock::new(|| {
@@ -42,13 +74,24 @@ pub static TEST_TRACING: std::sync::LazyLock<()> = std::sync::LazyLock::new(|| {
 
 pub struct AppHandle {
     pub handle: tokio::task::JoinHandle<Result<(), std::io::Error>>,
+    // not spawned for test apps, tests drive the delivery queue themselves
+    pub worker_handle: Option<tokio::task::JoinHandle<Result<()>>>,
     pub pool: PgPool,
     pub config: Settings,
+    pub email_client: Arc<EmailClient>,
 }
 
+pub struct ApplicationBaseUrl(pub String);
+
 impl AppHandle {
     pub async fn run_until_stopped(self) -> Result<()> {
-        self.handle.await??;
+        match self.worker_handle {
+            Some(worker_handle) => tokio::select! {
+                server = self.handle => server??,
+                worker = worker_handle => worker??,
+            },
+            None => self.handle.await??,
+        }
         Ok(())
     }
 }
@@ -65,22 +108,29 @@ pub async fn spawn_prod_app() -> Result<AppHandle> {
     set_global_default(subscriber).context("Failed to set subscriber")?;
 
     let config = get_configuration().context("Failed to read configuration")?;
-    build_app(config).await
+    build_app(config, true).await
 }
 
 pub async fn spawn_test_app() -> Result<AppHandle> {
+    spawn_test_app_with(|_| {}).await
+}
+
+/// Same as [`spawn_test_app`], but lets the caller adjust the configuration (e.g. to point the
+/// email client at a mock server) before the app is built
+pub async fn spawn_test_app_with(configure: impl FnOnce(&mut Settings)) -> Result<AppHandle> {
     // setup test logging
     LazyLock::force(&TEST_TRACING);
     let mut config = get_configuration().context("Failed to read configuration")?;
     debug!("Original config: {:?}", config);
     apply_testing_overrides(&mut config);
+    configure(&mut config);
     debug!("Testing config: {:?}", config);
     create_test_db(&config).await?;
     debug!("Created test db");
-    build_app(config).await
+    build_app(config, false).await
 }
 
-async fn build_app(mut config: Settings) -> Result<AppHandle> {
+async fn build_app(mut config: Settings, spawn_worker: bool) -> Result<AppHandle> {
     let address = format!("{}:{}", config.app.host, config.app.port);
     let listener =
         TcpListener::bind(&address).context(format!("Failed to bind to address: {}", address))?;
@@ -93,15 +143,21 @@ async fn build_app(mut config: Settings) -> Result<AppHandle> {
         .max_connections(config.database.max_connections.into())
         .connect_lazy_with(config.database.connection_options());
 
-    let mail_client = EmailClient::new(
-        SubscriberEmail::parse(config.email_client.sender_email.clone())
-            .context("Invalid sender email address")?,
-        config.email_client.base_url.clone(),
-        config.email_client.auth_token.clone(),
-        Duration::from_millis(config.email_client.timeout_milliseconds),
+    let email_client = Arc::new(
+        config
+            .email_client
+            .client()
+            .context("Invalid email client configuration")?,
     );
 
-    let server = run(listener, conn.clone(), mail_client).context("Failed to start server")?;
+    let server = run(
+        listener,
+        conn.clone(),
+        email_client.clone(),
+        config.app.base_url.clone(),
+        config.app.hmac_secret.clone(),
+        config.subscriptions.clone(),
+    )?;
     let handle = tokio::spawn(server);
 
     // Migrate the database
@@ -110,25 +166,176 @@ async fn build_app(mut config: Settings) -> Result<AppHandle> {
         .await
         .expect("Failed to migrate the database");
 
+    if let Some(admin) = &config.admin {
+        ensure_user(
+            &admin.username,
+            admin.password.clone(),
+            admin.email.as_deref(),
+            &conn,
+        )
+        .await
+        .context("Failed to bootstrap the admin user")?;
+    }
+
+    let worker_handle = spawn_worker.then(|| {
+        let pool = conn.clone();
+        let email_client = email_client.clone();
+        let base_url = config.app.base_url.clone();
+        tokio::spawn(async move { run_worker_until_stopped(pool, &email_client, &base_url).await })
+    });
+
     Ok(AppHandle {
         handle,
+        worker_handle,
         config,
         pool: conn,
+        email_client,
     })
 }
 
-fn run(listener: TcpListener, connection: PgPool, email_client: EmailClient) -> Result<Server> {
+fn run(
+    listener: TcpListener,
+    connection: PgPool,
+    email_client: Arc<EmailClient>,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    subscription_settings: SubscriptionSettings,
+) -> Result<Server> {
+    let secret_key = Key::try_from(hmac_secret.expose_secret().as_bytes())
+        .context("The HMAC secret must be at least 64 bytes long")?;
+    let message_store = CookieMessageStore::builder(secret_key.clone()).build();
+    let message_framework = FlashMessagesFramework::builder(message_store).build();
+    let session_store = PostgresSessionStore::new(connection.clone());
     let connection = web::Data::new(connection);
-    let email_client = web::Data::new(email_client);
+    let email_client = web::Data::from(email_client);
+    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let subscription_settings = web::Data::new(subscription_settings);
     Ok(HttpServer::new(move || {
         App::new()
+            .wrap(message_framework.clone())
+            .wrap(
+                SessionMiddleware::builder(session_store.clone(), secret_key.clone())
+                    .cookie_content_security(CookieContentSecurity::Signed)
+                    .build(),
+            )
             .wrap(TracingLogger::default())
+            .app_data(
+                web::JsonConfig::default().error_handler(|e, _| invalid_request(e, "invalid_body")),
+            )
+            .app_data(
+                web::FormConfig::default().error_handler(|e, _| invalid_request(e, "invalid_body")),
+            )
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|e, _| invalid_request(e, "invalid_query")),
+            )
             .route("/health_check", web::get().to(health_check))
+            .route("/login", web::get().to(login_form))
+            .route("/login", web::post().to(login))
+            .route("/login/two-factor", web::get().to(two_factor_form))
+            .route("/login/two-factor", web::post().to(two_factor))
+            .route("/password/forgot", web::get().to(forgot_password_form))
+            .route("/password/forgot", web::post().to(request_password_reset))
+            .route("/password/reset", web::get().to(reset_password_form))
+            .route("/password/reset", web::post().to(reset_password))
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(reject_anonymous_users))
+                    .route("/dashboard", web::get().to(admin_dashboard))
+                    .route("/logout", web::post().to(log_out))
+                    .route("/password", web::get().to(change_password_form))
+                    .route("/password", web::post().to(change_password))
+                    .route("/two-factor", web::get().to(two_factor_settings))
+                    .route("/two-factor/enroll", web::post().to(enroll_two_factor))
+                    .route("/two-factor/enable", web::post().to(enable_two_factor))
+                    .route("/two-factor/disable", web::post().to(disable_two_factor))
+                    .route(
+                        "/two-factor/recovery-codes",
+                        web::post().to(regenerate_two_factor_recovery_codes),
+                    )
+                    .route("/users", web::get().to(list_users))
+                    .route("/users", web::post().to(add_user))
+                    .route("/users/{user_id}/role", web::post().to(change_user_role))
+                    .route("/users/{user_id}/delete", web::post().to(delete_user))
+                    .route("/api-keys", web::get().to(list_api_keys))
+                    .route("/api-keys", web::post().to(add_api_key))
+                    .route(
+                        "/api-keys/{api_key_id}/revoke",
+                        web::post().to(revoke_api_key_action),
+                    )
+                    .route("/email-domains", web::get().to(list_email_domain_rules))
+                    .route("/email-domains", web::post().to(add_email_domain_rule))
+                    .route(
+                        "/email-domains/{domain}/delete",
+                        web::post().to(delete_email_domain_rule),
+                    )
+                    .route("/imports", web::get().to(list_imports))
+                    .route("/imports", web::post().to(upload_import))
+                    .route(
+                        "/imports/{import_id}/rejected.csv",
+                        web::get().to(download_rejected_rows),
+                    )
+                    .route("/lists", web::get().to(list_lists))
+                    .route("/lists", web::post().to(add_list))
+                    .route("/subscribers", web::get().to(list_subscribers))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(view_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/confirm",
+                        web::post().to(confirm_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/unsubscribe",
+                        web::post().to(unsubscribe_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        web::post().to(delete_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags",
+                        web::post().to(tag_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags/{tag}/delete",
+                        web::post().to(untag_subscriber),
+                    )
+                    .route("/tags", web::get().to(list_tags))
+                    .route("/tags/bulk", web::post().to(bulk_tag_action))
+                    .route("/tags/{tag}/delete", web::post().to(delete_tag_action)),
+            )
             .route("/subscriptions", web::post().to(subscribe))
+            .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/newsletters", web::post().to(publish_newsletter))
+            .service(
+                web::scope("/api/v1")
+                    .route("/stats", web::get().to(get_stats))
+                    .route("/subscribers", web::post().to(create_subscriber))
+                    .route(
+                        "/subscribers/{subscriber_id}/tags",
+                        web::post().to(add_subscriber_tags),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags/{tag}",
+                        web::delete().to(remove_subscriber_tag),
+                    )
+                    .route("/tags/bulk", web::post().to(bulk_tag_subscribers)),
+            )
             .app_data(connection.clone())
             .app_data(email_client.clone())
+            .app_data(base_url.clone())
+            .app_data(subscription_settings.clone())
     })
-    .listen(listener)?
+    .listen(listener)
+    .context("Failed to start server")?
     .run())
 }
 
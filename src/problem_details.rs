@@ -0,0 +1,90 @@
+//! Error bodies for the public and API routes, following RFC 9457 (`application/problem+json`).
+//! Internal causes never end up in the body, they are logged by the tracing middleware.
+
+use actix_web::{
+    HttpResponse, HttpResponseBuilder,
+    error::InternalError,
+    http::{StatusCode, header::CONTENT_TYPE},
+};
+use serde::Serialize;
+
+use crate::domain::FieldError;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    /// Always `about:blank`, the problem is identified by `code`
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: &'static str,
+    pub status: u16,
+    /// Stable, machine readable identifier of the problem
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, code: &'static str) -> ProblemDetails {
+        ProblemDetails {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            code,
+            detail: None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> ProblemDetails {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn errors(mut self, errors: Vec<FieldError>) -> ProblemDetails {
+        self.errors = errors;
+        self
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        self.response_builder().json(&self)
+    }
+
+    /// Builder with the status and content type set, for responses that need extra headers
+    pub fn response_builder(&self) -> HttpResponseBuilder {
+        let mut builder = HttpResponse::build(
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        );
+        builder.insert_header((CONTENT_TYPE, PROBLEM_JSON));
+        builder
+    }
+}
+
+/// Error handler for the `Json`, `Form` and `Query` extractors, the deserialization message
+/// tells the client which field is missing or malformed
+pub fn invalid_request<E>(e: E, code: &'static str) -> actix_web::Error
+where
+    E: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    let response = ProblemDetails::new(StatusCode::BAD_REQUEST, code)
+        .detail(e.to_string())
+        .into_response();
+    InternalError::from_response(e, response).into()
+}
+
+/// `Debug` implementation for error enums that prints the whole chain of causes
+pub fn error_chain_fmt(
+    e: &impl std::error::Error,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    writeln!(f, "{}\n", e)?;
+    let mut current = e.source();
+    while let Some(cause) = current {
+        writeln!(f, "Caused by:\n\t{}", cause)?;
+        current = cause.source();
+    }
+    Ok(())
+}
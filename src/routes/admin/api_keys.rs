@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{ApiScope, Owner, RequireRole, UserId, create_api_key, revoke_api_key},
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+/// Longest lifetime that can be picked for a key, keys without an expiry are still allowed
+const MAX_API_KEY_LIFETIME_DAYS: i64 = 3650;
+
+struct ApiKeyRow {
+    api_key_id: Uuid,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_by: String,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+#[instrument(name = "Listing API keys", skip_all)]
+pub async fn list_api_keys(
+    _: RequireRole<Owner>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let keys = match get_api_keys(&pool).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            error!("Failed to list API keys: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let format_time = |t: Option<DateTime<Utc>>| {
+        t.map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M").to_string())
+    };
+    let rows_html: String = keys
+        .iter()
+        .map(|k| {
+            let action = match k.revoked_at {
+                Some(revoked_at) => format!("revoked {}", format_time(Some(revoked_at))),
+                None => format!(
+                    r#"<form action="/admin/api-keys/{}/revoke" method="post"><input type="submit" value="Revoke"></form>"#,
+                    k.api_key_id
+                ),
+            };
+            format!(
+                r#"    <tr>
+        <td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>
+        <td>{action}</td>
+    </tr>
+"#,
+                htmlescape::encode_minimal(&k.name),
+                k.prefix,
+                htmlescape::encode_minimal(&k.scopes.join(", ")),
+                htmlescape::encode_minimal(&k.created_by),
+                format_time(Some(k.created_at)),
+                format_time(k.expires_at),
+                format_time(k.last_used_at),
+            )
+        })
+        .collect();
+    let scopes_html: String = ApiScope::ALL
+        .iter()
+        .map(|s| format!(r#"    <label><input type="checkbox" name="{s}"> {s}</label>"#) + "\n")
+        .collect();
+
+    html_page(
+        "API keys",
+        &format!(
+            r#"{}<h1>API keys</h1>
+<table>
+    <tr><th>Name</th><th>Prefix</th><th>Scopes</th><th>Created by</th><th>Created</th><th>Expires</th><th>Last used</th><th></th></tr>
+{rows_html}</table>
+<h2>Create a key</h2>
+<form action="/admin/api-keys" method="post">
+    <label>Name <input type="text" name="name"></label>
+{scopes_html}    <label>Expires in (days, empty for never) <input type="number" name="expires_in_days" min="1" max="{MAX_API_KEY_LIFETIME_DAYS}"></label>
+    <button type="submit">Create key</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+/// Scopes are sent as one checkbox per scope, named after it. The key is shown once, only its
+/// hash is kept.
+#[instrument(name = "Creating an API key", skip_all, fields(user_id = %*user_id))]
+pub async fn add_api_key(
+    _: RequireRole<Owner>,
+    user_id: web::ReqData<UserId>,
+    form: web::Form<HashMap<String, String>>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let name = form.get("name").map_or("", |n| n.trim());
+    if name.is_empty() {
+        FlashMessage::error("The key needs a name.").send();
+        return see_other("/admin/api-keys");
+    }
+    let scopes: Vec<ApiScope> = ApiScope::ALL
+        .into_iter()
+        .filter(|s| form.contains_key(s.as_str()))
+        .collect();
+    if scopes.is_empty() {
+        FlashMessage::error("Pick at least one scope.").send();
+        return see_other("/admin/api-keys");
+    }
+    let expires_at = match form.get("expires_in_days").map_or("", |d| d.trim()) {
+        "" => None,
+        days => match days.parse::<i64>() {
+            Ok(days) if (1..=MAX_API_KEY_LIFETIME_DAYS).contains(&days) => {
+                Some(Utc::now() + Duration::days(days))
+            }
+            _ => {
+                FlashMessage::error(format!(
+                    "The expiry must be between 1 and {} days.",
+                    MAX_API_KEY_LIFETIME_DAYS
+                ))
+                .send();
+                return see_other("/admin/api-keys");
+            }
+        },
+    };
+
+    let key = match create_api_key(&pool, **user_id, name, &scopes, expires_at).await {
+        Ok((api_key_id, key)) => {
+            info!(%api_key_id, "Created API key");
+            key
+        }
+        Err(e) => {
+            error!("Failed to create API key: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    html_page(
+        "API key created",
+        &format!(
+            r#"<h1>API key created</h1>
+<p>Copy the key for {} now, it won't be shown again.</p>
+<p><code id="api-key">{key}</code></p>
+<p>Send it as <code>Authorization: Bearer &lt;key&gt;</code>.</p>
+<p><a href="/admin/api-keys">&lt;- Back to API keys</a></p>"#,
+            htmlescape::encode_minimal(name),
+        ),
+    )
+}
+
+#[instrument(name = "Revoking an API key", skip(pool))]
+pub async fn revoke_api_key_action(
+    _: RequireRole<Owner>,
+    api_key_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match revoke_api_key(&pool, *api_key_id).await {
+        Ok(true) => {
+            FlashMessage::info("The API key has been revoked.").send();
+            see_other("/admin/api-keys")
+        }
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("Failed to revoke API key: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_api_keys(pool: &PgPool) -> Result<Vec<ApiKeyRow>> {
+    sqlx::query_as!(
+        ApiKeyRow,
+        r#"
+        SELECT k.api_key_id, k.name, k.prefix, k.scopes, u.username AS created_by,
+               k.created_at, k.expires_at, k.last_used_at, k.revoked_at
+        FROM api_keys k
+        JOIN users u ON u.user_id = k.user_id
+        ORDER BY k.created_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve API keys")
+}
@@ -0,0 +1,110 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Role, UserId},
+    domain::SubscriberStatus,
+    utils::{flash_messages_html, html_page},
+};
+
+#[instrument(
+    name = "Rendering the admin dashboard",
+    skip(user_id, role, pool, flash_messages),
+    fields(user_id = %*user_id)
+)]
+pub async fn admin_dashboard(
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let username = match get_username(**user_id, &pool).await {
+        Ok(username) => username,
+        Err(e) => {
+            error!("Failed to load the logged in user: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let counts = match count_subscribers_by_status(&pool).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            error!("Failed to count subscribers: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let counts_html: String = counts
+        .iter()
+        .map(|(status, count)| {
+            format!(
+                r#"    <tr><td><a href="/admin/subscribers?status={status}">{status}</a></td><td>{count}</td></tr>
+"#
+            )
+        })
+        .collect();
+
+    let users_html = match *role {
+        Role::Owner => {
+            r#"<p><a href="/admin/users">Manage users</a></p>
+<p><a href="/admin/api-keys">API keys</a></p>"#
+        }
+        _ => "",
+    };
+
+    html_page(
+        "Admin dashboard",
+        &format!(
+            r#"{}<p>Welcome {}! You are signed in as {}.</p>
+<h2>Subscribers</h2>
+<table>
+    <tr><th>Status</th><th>Count</th></tr>
+{counts_html}</table>
+<p><a href="/admin/subscribers">Manage subscribers</a></p>
+<p><a href="/admin/imports">Import subscribers</a></p>
+<p><a href="/admin/lists">Lists</a></p>
+<p><a href="/admin/tags">Tags</a></p>
+<p><a href="/admin/email-domains">Email domains</a></p>
+<p><a href="/admin/password">Change password</a></p>
+<p><a href="/admin/two-factor">Two-factor authentication</a></p>
+{users_html}
+<form name="logoutForm" action="/admin/logout" method="post">
+    <input type="submit" value="Logout">
+</form>"#,
+            flash_messages_html(&flash_messages),
+            htmlescape::encode_minimal(&username),
+            *role,
+        ),
+    )
+}
+
+pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String> {
+    let row = sqlx::query!(r#"SELECT username FROM users WHERE user_id = $1"#, user_id,)
+        .fetch_one(pool)
+        .await
+        .context("Failed to retrieve the username")?;
+    Ok(row.username)
+}
+
+/// Number of subscribers for every known status, including the empty ones
+pub async fn count_subscribers_by_status(pool: &PgPool) -> Result<Vec<(SubscriberStatus, i64)>> {
+    let rows =
+        sqlx::query!(r#"SELECT status, COUNT(*) AS "count!" FROM subscriptions GROUP BY status"#,)
+            .fetch_all(pool)
+            .await
+            .context("Failed to count subscribers by status")?;
+
+    Ok(SubscriberStatus::ALL
+        .into_iter()
+        .map(|status| {
+            let count = rows
+                .iter()
+                .find(|r| r.status == status.as_str())
+                .map_or(0, |r| r.count);
+            (status, count)
+        })
+        .collect())
+}
@@ -0,0 +1,196 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+
+use crate::{
+    authentication::{Editor, RequireRole, Role},
+    domain::DomainRule,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct DomainRuleFormData {
+    domain: String,
+    rule: DomainRule,
+}
+
+struct DomainRuleRow {
+    domain: String,
+    rule: String,
+}
+
+#[instrument(name = "Listing email domain rules", skip_all)]
+pub async fn list_email_domain_rules(
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let rules = match get_domain_rules(&pool).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            error!("Failed to list email domain rules: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let can_edit = *role >= Role::Editor;
+
+    let rows_html: String = rules
+        .iter()
+        .map(|r| {
+            let action = match can_edit {
+                true => format!(
+                    r#"<form action="/admin/email-domains/{}/delete" method="post"><input type="submit" value="Remove"></form>"#,
+                    htmlescape::encode_minimal(&r.domain)
+                ),
+                false => String::new(),
+            };
+            format!(
+                "    <tr><td>{}</td><td>{}</td><td>{action}</td></tr>\n",
+                htmlescape::encode_minimal(&r.domain),
+                r.rule,
+            )
+        })
+        .collect();
+    let form_html = match can_edit {
+        true => {
+            let rule_options: String = DomainRule::ALL
+                .iter()
+                .map(|r| format!(r#"<option value="{r}">{r}</option>"#))
+                .collect();
+            format!(
+                r#"<h2>Add a rule</h2>
+<form action="/admin/email-domains" method="post">
+    <label>Domain <input type="text" name="domain" placeholder="example.com"></label>
+    <label>Rule <select name="rule">{rule_options}</select></label>
+    <button type="submit">Add rule</button>
+</form>
+"#
+            )
+        }
+        false => String::new(),
+    };
+
+    html_page(
+        "Email domains",
+        &format!(
+            r#"{}<h1>Email domains</h1>
+<p>Signups from known disposable email providers are rejected. Blocked domains are rejected too,
+allowed ones are accepted even if they are on the disposable list. Rules apply to subdomains as
+well.</p>
+<table>
+    <tr><th>Domain</th><th>Rule</th><th></th></tr>
+{rows_html}</table>
+{form_html}<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+/// Replaces any existing rule for the domain
+#[instrument(name = "Adding an email domain rule", skip_all, fields(domain = %form.domain, rule = %form.rule))]
+pub async fn add_email_domain_rule(
+    _: RequireRole<Editor>,
+    form: web::Form<DomainRuleFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let domain = match normalize_domain(&form.domain) {
+        Some(domain) => domain,
+        None => {
+            FlashMessage::error(format!("{} is not a valid domain.", form.domain.trim())).send();
+            return see_other("/admin/email-domains");
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO email_domain_rules (domain, rule)
+        VALUES ($1, $2)
+        ON CONFLICT (domain) DO UPDATE SET rule = EXCLUDED.rule, created_at = now()
+        "#,
+        domain,
+        form.rule.as_str(),
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(_) => {
+            info!("Saved email domain rule");
+            FlashMessage::info(format!("Added a {} rule for {}.", form.rule, domain)).send();
+            see_other("/admin/email-domains")
+        }
+        Err(e) => {
+            error!("Failed to execute query: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[instrument(name = "Removing an email domain rule", skip(pool))]
+pub async fn delete_email_domain_rule(
+    _: RequireRole<Editor>,
+    domain: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let result = sqlx::query!(
+        r#"DELETE FROM email_domain_rules WHERE domain = $1"#,
+        domain.as_str(),
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            FlashMessage::info("The rule has been removed.").send();
+            see_other("/admin/email-domains")
+        }
+        Err(e) => {
+            error!("Failed to execute query: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Same normalization as the domain of a `SubscriberEmail`, so that rules match stored addresses
+fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_start_matches('@');
+    if domain.is_empty() || domain.contains('@') {
+        return None;
+    }
+    let domain = idna::domain_to_ascii(domain).ok()?;
+    let valid = domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(domain)
+}
+
+async fn get_domain_rules(pool: &PgPool) -> Result<Vec<DomainRuleRow>> {
+    sqlx::query_as!(
+        DomainRuleRow,
+        r#"SELECT domain, rule FROM email_domain_rules ORDER BY domain"#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve email domain rules")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_domain;
+
+    #[test]
+    fn domains_are_normalized_like_email_domains() {
+        assert_eq!(
+            normalize_domain(" @Bücher.Example "),
+            Some("xn--bcher-kva.example".to_string())
+        );
+        assert_eq!(normalize_domain("ursula@example.com"), None);
+        assert_eq!(normalize_domain("localhost"), None);
+        assert_eq!(normalize_domain("exa mple.com"), None);
+    }
+}
@@ -0,0 +1,249 @@
+use actix_multipart::form::{MultipartForm, tempfile::TempFile, text::Text};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Editor, RequireRole, Role, UserId},
+    configuration::SubscriptionSettings,
+    domain::{MailingList, SubscriberStatus},
+    subscriber_import::{ImportError, import_subscribers, save_import},
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+/// Imports shown on the imports page, newest first
+const RECENT_IMPORTS: i64 = 20;
+
+#[derive(MultipartForm)]
+pub struct ImportFormData {
+    file: TempFile,
+    list: Text<String>,
+    status: Text<String>,
+}
+
+struct ImportRow {
+    import_id: Uuid,
+    file_name: String,
+    list_name: String,
+    status: String,
+    n_created: i32,
+    n_updated: i32,
+    n_rejected: i32,
+    created_at: DateTime<Utc>,
+}
+
+struct ListOption {
+    slug: String,
+    name: String,
+}
+
+/// Recent imports with their rejected rows, and the upload form
+#[instrument(name = "Listing imports", skip_all)]
+pub async fn list_imports(
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let imports = match get_imports(&pool).await {
+        Ok(imports) => imports,
+        Err(e) => {
+            error!("Failed to list imports: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let lists = match get_list_options(&pool).await {
+        Ok(lists) => lists,
+        Err(e) => {
+            error!("Failed to list lists: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let rows_html: String = imports
+        .iter()
+        .map(|i| {
+            let report_html = match i.n_rejected {
+                0 => "0".to_string(),
+                n => format!(
+                    r#"<a href="/admin/imports/{}/rejected.csv">{n}</a>"#,
+                    i.import_id
+                ),
+            };
+            format!(
+                "    <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{report_html}</td></tr>\n",
+                i.created_at.to_rfc3339(),
+                htmlescape::encode_minimal(&i.file_name),
+                htmlescape::encode_minimal(&i.list_name),
+                htmlescape::encode_minimal(&i.status),
+                i.n_created,
+                i.n_updated,
+            )
+        })
+        .collect();
+    let form_html = match *role >= Role::Editor {
+        true => {
+            let list_options: String = lists
+                .iter()
+                .map(|l| {
+                    format!(
+                        r#"<option value="{}">{}</option>"#,
+                        htmlescape::encode_attribute(&l.slug),
+                        htmlescape::encode_minimal(&l.name),
+                    )
+                })
+                .collect();
+            let status_options: String = SubscriberStatus::ALL
+                .iter()
+                .map(|s| format!(r#"<option value="{s}">{s}</option>"#))
+                .collect();
+            format!(
+                r#"<h2>Import a CSV file</h2>
+<p>The file needs a header row with an <code>email</code> column, and either a <code>name</code>
+column or <code>first name</code> and <code>last name</code> columns. Columns named after a custom
+attribute are imported too, other columns are ignored. No confirmation emails are sent.</p>
+<form action="/admin/imports" method="post" enctype="multipart/form-data">
+    <label>List <select name="list">{list_options}</select></label>
+    <label>Status of new subscribers <select name="status">{status_options}</select></label>
+    <label>File <input type="file" name="file" accept=".csv,text/csv"></label>
+    <button type="submit">Import</button>
+</form>
+"#
+            )
+        }
+        false => String::new(),
+    };
+
+    html_page(
+        "Imports",
+        &format!(
+            r#"{}<h1>Imports</h1>
+<table>
+    <tr><th>Imported at</th><th>File</th><th>List</th><th>Status</th><th>Created</th><th>Updated</th><th>Rejected</th></tr>
+{rows_html}</table>
+{form_html}<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+/// Existing subscribers keep their status, only their custom attributes are updated
+#[instrument(
+    name = "Uploading subscribers",
+    skip_all,
+    fields(user_id = %*user_id, file_name = ?form.file.file_name, size = form.file.size)
+)]
+pub async fn upload_import(
+    _: RequireRole<Editor>,
+    user_id: web::ReqData<UserId>,
+    MultipartForm(form): MultipartForm<ImportFormData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> HttpResponse {
+    let status = match SubscriberStatus::parse(&form.status) {
+        Ok(status) => status,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return see_other("/admin/imports");
+        }
+    };
+    let list = match MailingList::find(&pool, Some(&form.list)).await {
+        Ok(Some(list)) => list,
+        Ok(None) => {
+            FlashMessage::error(format!("There is no list called {}.", *form.list)).send();
+            return see_other("/admin/imports");
+        }
+        Err(e) => {
+            error!("Failed to retrieve the list: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let file = match tokio::fs::File::open(form.file.file.path()).await {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Failed to open the uploaded file: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let summary = match import_subscribers(&pool, &settings, &list, status, file).await {
+        Ok(summary) => summary,
+        Err(ImportError::Unexpected(e)) => {
+            error!("Failed to import subscribers: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+        Err(e) => {
+            FlashMessage::error(format!("{}.", e)).send();
+            return see_other("/admin/imports");
+        }
+    };
+    let file_name = form.file.file_name.as_deref().unwrap_or("upload.csv");
+    if let Err(e) = save_import(&pool, Some(**user_id), file_name, &list, status, &summary).await {
+        error!("Failed to save the import: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    FlashMessage::info(format!(
+        "{} subscribers have been created, {} updated and {} rows rejected.",
+        summary.n_created,
+        summary.n_updated,
+        summary.rejected.len()
+    ))
+    .send();
+    see_other("/admin/imports")
+}
+
+#[instrument(name = "Downloading rejected rows", skip(pool))]
+pub async fn download_rejected_rows(
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let report = sqlx::query!(
+        r#"SELECT rejected_report FROM subscriber_imports WHERE import_id = $1"#,
+        *import_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await;
+    match report {
+        Ok(Some(r)) => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((
+                "Content-Disposition",
+                format!(r#"attachment; filename="rejected-{}.csv""#, import_id),
+            ))
+            .body(r.rejected_report),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("Failed to retrieve the rejected rows: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_imports(pool: &PgPool) -> Result<Vec<ImportRow>> {
+    sqlx::query_as!(
+        ImportRow,
+        r#"
+        SELECT
+            i.import_id, i.file_name, l.name AS list_name, i.status,
+            i.n_created, i.n_updated, i.n_rejected, i.created_at
+        FROM subscriber_imports i
+        JOIN lists l ON l.list_id = i.list_id
+        ORDER BY i.created_at DESC
+        LIMIT $1
+        "#,
+        RECENT_IMPORTS,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve imports")
+}
+
+async fn get_list_options(pool: &PgPool) -> Result<Vec<ListOption>> {
+    sqlx::query_as!(ListOption, r#"SELECT slug, name FROM lists ORDER BY name"#,)
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve lists")
+}
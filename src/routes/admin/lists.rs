@@ -0,0 +1,152 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Editor, RequireRole, Role},
+    domain::ListSlug,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct ListFormData {
+    slug: String,
+    name: String,
+}
+
+struct ListRow {
+    slug: String,
+    name: String,
+    pending: i64,
+    confirmed: i64,
+    unsubscribed: i64,
+}
+
+/// Every list with its members counted by status
+#[instrument(name = "Listing lists", skip_all)]
+pub async fn list_lists(
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let lists = match get_lists(&pool).await {
+        Ok(lists) => lists,
+        Err(e) => {
+            error!("Failed to list lists: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let rows_html: String = lists
+        .iter()
+        .map(|l| {
+            format!(
+                "    <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                htmlescape::encode_minimal(&l.name),
+                htmlescape::encode_minimal(&l.slug),
+                l.pending,
+                l.confirmed,
+                l.unsubscribed,
+            )
+        })
+        .collect();
+    let form_html = match *role >= Role::Editor {
+        true => r#"<h2>Add a list</h2>
+<form action="/admin/lists" method="post">
+    <label>Name <input type="text" name="name" placeholder="Weekly digest"></label>
+    <label>Slug <input type="text" name="slug" placeholder="weekly-digest"></label>
+    <button type="submit">Add list</button>
+</form>
+"#
+        .to_string(),
+        false => String::new(),
+    };
+
+    html_page(
+        "Lists",
+        &format!(
+            r#"{}<h1>Lists</h1>
+<p>Subscribers join a list through the <code>list</code> field of the signup form, issues are
+published to a list through the <code>list</code> field of the request body. Both default to
+the <code>newsletter</code> list.</p>
+<table>
+    <tr><th>Name</th><th>Slug</th><th>Pending</th><th>Confirmed</th><th>Unsubscribed</th></tr>
+{rows_html}</table>
+{form_html}<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+#[instrument(name = "Adding a list", skip_all, fields(slug = %form.slug))]
+pub async fn add_list(
+    _: RequireRole<Editor>,
+    form: web::Form<ListFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let slug = match ListSlug::parse(&form.slug) {
+        Ok(slug) => slug,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return see_other("/admin/lists");
+        }
+    };
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The name can't be empty.").send();
+        return see_other("/admin/lists");
+    }
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO lists (list_id, slug, name)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name,
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => {
+            FlashMessage::error(format!("There is already a list called {}.", slug)).send()
+        }
+        Ok(_) => {
+            info!("Created list");
+            FlashMessage::info(format!("{} has been added.", name)).send();
+        }
+        Err(e) => {
+            error!("Failed to execute query: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other("/admin/lists")
+}
+
+async fn get_lists(pool: &PgPool) -> Result<Vec<ListRow>> {
+    sqlx::query_as!(
+        ListRow,
+        r#"
+        SELECT
+            l.slug,
+            l.name,
+            COUNT(*) FILTER (WHERE m.status = 'pending_confirmation') AS "pending!",
+            COUNT(*) FILTER (WHERE m.status = 'confirmed') AS "confirmed!",
+            COUNT(*) FILTER (WHERE m.status = 'unsubscribed') AS "unsubscribed!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.list_id
+        GROUP BY l.list_id
+        ORDER BY l.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve lists")
+}
@@ -0,0 +1,23 @@
+pub mod api_keys;
+pub mod dashboard;
+pub mod email_domains;
+pub mod imports;
+pub mod lists;
+pub mod logout;
+pub mod password;
+pub mod subscribers;
+pub mod tags;
+pub mod two_factor;
+pub mod users;
+
+pub use api_keys::*;
+pub use dashboard::*;
+pub use email_domains::*;
+pub use imports::*;
+pub use lists::*;
+pub use logout::*;
+pub use password::*;
+pub use subscribers::*;
+pub use tags::*;
+pub use two_factor::*;
+pub use users::*;
@@ -0,0 +1,91 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, instrument};
+
+use crate::{
+    authentication::{
+        AuthError, Credentials, UserId, change_password as store_new_password,
+        validate_credentials, validate_new_password,
+    },
+    routes::get_username,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct ChangePasswordFormData {
+    current_password: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+pub async fn change_password_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    html_page(
+        "Change Password",
+        &format!(
+            r#"{}
+<form action="/admin/password" method="post">
+    <label>Current password
+        <input type="password" placeholder="Enter current password" name="current_password">
+    </label>
+    <br>
+    <label>New password
+        <input type="password" placeholder="Enter new password" name="new_password">
+    </label>
+    <br>
+    <label>Confirm new password
+        <input type="password" placeholder="Type the new password again" name="new_password_check">
+    </label>
+    <br>
+    <button type="submit">Change password</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages)
+        ),
+    )
+}
+
+#[instrument(name = "Changing password", skip(form, user_id, pool), fields(user_id = %*user_id))]
+pub async fn change_password(
+    form: web::Form<ChangePasswordFormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = **user_id;
+    if let Err(e) = validate_new_password(&form.new_password, &form.new_password_check) {
+        FlashMessage::error(e.to_string()).send();
+        return see_other("/admin/password");
+    }
+
+    let username = match get_username(user_id, &pool).await {
+        Ok(username) => username,
+        Err(e) => {
+            error!("Failed to load the logged in user: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let credentials = Credentials {
+        username,
+        password: form.0.current_password,
+    };
+    match validate_credentials(credentials, &pool).await {
+        Ok(_) => {}
+        Err(AuthError::InvalidCredentials(_)) => {
+            FlashMessage::error("The current password is incorrect.").send();
+            return see_other("/admin/password");
+        }
+        Err(AuthError::UnexpectedError(e)) => {
+            error!("Failed to validate credentials: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    if let Err(e) = store_new_password(user_id, form.0.new_password, pool.get_ref()).await {
+        error!("Failed to change password: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    FlashMessage::info("Your password has been changed.").send();
+    see_other("/admin/password")
+}
@@ -0,0 +1,476 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Editor, RequireRole, Role},
+    domain::SubscriberStatus,
+    subscriber_export::{self, ExportFormat},
+    tags::get_subscriber_tags,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+/// Rows shown on a single page of the subscribers list
+pub const SUBSCRIBERS_PAGE_SIZE: i64 = 25;
+
+#[derive(Deserialize, Debug)]
+pub struct ListParameters {
+    status: Option<SubscriberStatus>,
+    page: Option<u32>,
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+    attributes: serde_json::Value,
+}
+
+struct MembershipRow {
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportParameters {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Paginated list of subscribers, newest first, optionally filtered by status
+#[instrument(name = "Listing subscribers", skip(role, pool, flash_messages))]
+pub async fn list_subscribers(
+    role: web::ReqData<Role>,
+    parameters: web::Query<ListParameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let status = parameters.status;
+    let page = parameters.page.unwrap_or(1).max(1);
+
+    let (subscribers, total) = match get_subscribers_page(&pool, status, page).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to list subscribers: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let status_query = status.map_or(String::new(), |s| format!("status={s}&"));
+    let filters_html: String = std::iter::once(r#"<a href="/admin/subscribers">all</a>"#.into())
+        .chain(
+            SubscriberStatus::ALL
+                .iter()
+                .map(|s| format!(r#"<a href="/admin/subscribers?status={s}">{s}</a>"#)),
+        )
+        .collect::<Vec<String>>()
+        .join(" | ");
+    let rows_html: String = subscribers
+        .iter()
+        .map(|s| {
+            format!(
+                r#"    <tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>
+"#,
+                s.id,
+                htmlescape::encode_minimal(&s.email),
+                htmlescape::encode_minimal(&s.name),
+                htmlescape::encode_minimal(&s.status),
+                s.subscribed_at.to_rfc3339(),
+            )
+        })
+        .collect();
+
+    let n_pages = ((total + SUBSCRIBERS_PAGE_SIZE - 1) / SUBSCRIBERS_PAGE_SIZE).max(1);
+    let mut pagination_html = format!("Page {page} of {n_pages}");
+    if page > 1 {
+        pagination_html.push_str(&format!(
+            r#" <a href="/admin/subscribers?{status_query}page={}">Previous</a>"#,
+            page - 1
+        ));
+    }
+    if i64::from(page) < n_pages {
+        pagination_html.push_str(&format!(
+            r#" <a href="/admin/subscribers?{status_query}page={}">Next</a>"#,
+            page + 1
+        ));
+    }
+
+    let export_html = match *role >= Role::Editor {
+        true => {
+            r#"<p>Export all subscribers as <a href="/admin/subscribers/export?format=csv">CSV</a> or
+<a href="/admin/subscribers/export?format=ndjson">JSON Lines</a></p>
+"#
+        }
+        false => "",
+    };
+
+    html_page(
+        "Subscribers",
+        &format!(
+            r#"{}<h1>Subscribers</h1>
+<p>{filters_html}</p>
+<p>{total} subscribers</p>
+<table>
+    <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th></tr>
+{rows_html}</table>
+<p>{pagination_html}</p>
+{export_html}<p><a href="/admin/dashboard">Back to the dashboard</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+/// Streams every subscriber with their lists and tags, as an attachment
+#[instrument(name = "Downloading subscribers", skip(pool))]
+pub async fn export_subscribers(
+    _: RequireRole<Editor>,
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let format = parameters.format;
+    let stream = match subscriber_export::export_subscribers(&pool, format).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to export subscribers: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // the status is already sent when a batch fails, all we can do is cut the response short
+    let stream = stream.inspect(|chunk| {
+        if let Err(e) = chunk {
+            error!("Failed to export subscribers: {:?}", e);
+        }
+    });
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                r#"attachment; filename="subscribers-{}.{}""#,
+                Utc::now().format("%Y-%m-%d"),
+                format.extension()
+            ),
+        ))
+        .streaming(stream)
+}
+
+#[instrument(name = "Viewing a subscriber", skip(role, pool, flash_messages))]
+pub async fn view_subscriber(
+    role: web::ReqData<Role>,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let subscriber = match get_subscriber(&pool, *subscriber_id).await {
+        Ok(Some(subscriber)) => subscriber,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("Failed to retrieve subscriber: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let memberships = match get_memberships(&pool, subscriber.id).await {
+        Ok(memberships) => memberships,
+        Err(e) => {
+            error!("Failed to retrieve list memberships: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let tags = match get_subscriber_tags(&pool, subscriber.id).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            error!("Failed to retrieve subscriber tags: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let memberships_html: String = memberships
+        .iter()
+        .map(|m| {
+            format!(
+                "    <tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                htmlescape::encode_minimal(&m.name),
+                htmlescape::encode_minimal(&m.status),
+                m.subscribed_at.to_rfc3339(),
+            )
+        })
+        .collect();
+
+    let action = |action: &str, label: &str| {
+        format!(
+            r#"<form action="/admin/subscribers/{}/{action}" method="post"><input type="submit" value="{label}"></form>"#,
+            subscriber.id
+        )
+    };
+    let tags_html: String = tags
+        .iter()
+        .map(|tag| {
+            let tag = htmlescape::encode_minimal(tag);
+            match *role >= Role::Editor {
+                true => format!(
+                    r#"    <li>{tag} <form action="/admin/subscribers/{}/tags/{tag}/delete" method="post"><input type="submit" value="Remove"></form></li>
+"#,
+                    subscriber.id
+                ),
+                false => format!("    <li>{tag}</li>\n"),
+            }
+        })
+        .collect();
+    // viewers can look but not touch
+    let mut actions_html = String::new();
+    let mut tag_form_html = String::new();
+    if *role >= Role::Editor {
+        tag_form_html = format!(
+            r#"<form action="/admin/subscribers/{}/tags" method="post">
+    <label>Tags <input type="text" name="tags" placeholder="beta, conference-2026"></label>
+    <button type="submit">Add tags</button>
+</form>
+"#,
+            subscriber.id
+        );
+        if subscriber.status == SubscriberStatus::PendingConfirmation.as_str() {
+            actions_html.push_str(&action("confirm", "Confirm"));
+        }
+        if subscriber.status != SubscriberStatus::Unsubscribed.as_str() {
+            actions_html.push_str(&action("unsubscribe", "Unsubscribe"));
+        }
+        actions_html.push_str(&action("delete", "Delete"));
+    }
+
+    html_page(
+        "Subscriber",
+        &format!(
+            r#"{}<h1>{}</h1>
+<dl>
+    <dt>Name</dt><dd>{}</dd>
+    <dt>Status</dt><dd>{}</dd>
+    <dt>Subscribed at</dt><dd>{}</dd>
+    <dt>Unsubscribed at</dt><dd>{}</dd>
+    <dt>Attributes</dt><dd><code>{}</code></dd>
+</dl>
+<h2>Lists</h2>
+<table>
+    <tr><th>List</th><th>Status</th><th>Subscribed at</th></tr>
+{memberships_html}</table>
+<h2>Tags</h2>
+<ul>
+{tags_html}</ul>
+{tag_form_html}{actions_html}
+<p><a href="/admin/subscribers">Back to subscribers</a></p>"#,
+            flash_messages_html(&flash_messages),
+            htmlescape::encode_minimal(&subscriber.email),
+            htmlescape::encode_minimal(&subscriber.name),
+            htmlescape::encode_minimal(&subscriber.status),
+            subscriber.subscribed_at.to_rfc3339(),
+            subscriber
+                .unsubscribed_at
+                .map_or("-".into(), |t| t.to_rfc3339()),
+            htmlescape::encode_minimal(&subscriber.attributes.to_string()),
+        ),
+    )
+}
+
+/// Confirms a pending subscriber on their behalf, e.g. when the confirmation email got lost. Only
+/// the lists they are still pending on are confirmed.
+#[instrument(name = "Manually confirming a subscriber", skip(pool))]
+pub async fn confirm_subscriber(
+    _: RequireRole<Editor>,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match mark_confirmed(&pool, *subscriber_id).await {
+        Ok(false) => {
+            FlashMessage::error("Only subscribers pending confirmation can be confirmed.").send()
+        }
+        Ok(true) => FlashMessage::info("The subscriber has been confirmed.").send(),
+        Err(e) => {
+            error!("Failed to confirm subscriber: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other(&format!("/admin/subscribers/{}", subscriber_id))
+}
+
+/// Removes the subscriber from every list
+#[instrument(name = "Manually unsubscribing a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(
+    _: RequireRole<Editor>,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match mark_unsubscribed(&pool, *subscriber_id).await {
+        Ok(false) => return HttpResponse::NotFound().finish(),
+        Ok(true) => FlashMessage::info("The subscriber has been unsubscribed.").send(),
+        Err(e) => {
+            error!("Failed to unsubscribe subscriber: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other(&format!("/admin/subscribers/{}", subscriber_id))
+}
+
+/// Removes the subscriber along with their tokens and pending deliveries
+#[instrument(name = "Deleting a subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    _: RequireRole<Editor>,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match remove_subscriber(&pool, *subscriber_id).await {
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Ok(true) => {
+            FlashMessage::info("The subscriber has been deleted.").send();
+            see_other("/admin/subscribers")
+        }
+        Err(e) => {
+            error!("Failed to delete subscriber: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_subscribers_page(
+    pool: &PgPool,
+    status: Option<SubscriberStatus>,
+    page: u32,
+) -> Result<(Vec<SubscriberRow>, i64)> {
+    let status = status.map(|s| s.as_str());
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at, unsubscribed_at, attributes
+        FROM subscriptions
+        WHERE $1::text IS NULL OR status = $1
+        ORDER BY subscribed_at DESC, id
+        LIMIT $2 OFFSET $3
+        "#,
+        status,
+        SUBSCRIBERS_PAGE_SIZE,
+        (i64::from(page) - 1) * SUBSCRIBERS_PAGE_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers")?;
+
+    let total = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriptions WHERE $1::text IS NULL OR status = $1"#,
+        status,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count subscribers")?
+    .count;
+
+    Ok((subscribers, total))
+}
+
+async fn get_memberships(pool: &PgPool, subscriber_id: Uuid) -> Result<Vec<MembershipRow>> {
+    sqlx::query_as!(
+        MembershipRow,
+        r#"
+        SELECT l.name, m.status, m.subscribed_at
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve list memberships")
+}
+
+async fn get_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<Option<SubscriberRow>> {
+    sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at, unsubscribed_at, attributes
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber")
+}
+
+/// Returns whether the subscriber was pending confirmation
+async fn mark_confirmed(pool: &PgPool, subscriber_id: Uuid) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    // never re-subscribe someone who opted out
+    let n_confirmed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed'
+        WHERE id = $1 AND status = 'pending_confirmation'
+        "#,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'confirmed'
+        WHERE subscriber_id = $1 AND status = 'pending_confirmation'
+        "#,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(n_confirmed > 0)
+}
+
+/// Returns whether the subscriber existed
+async fn mark_unsubscribed(pool: &PgPool, subscriber_id: Uuid) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    let now = Utc::now();
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed', unsubscribed_at = COALESCE(unsubscribed_at, $2)
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        now,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'unsubscribed', unsubscribed_at = COALESCE(unsubscribed_at, $2)
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id,
+        now,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(n_updated > 0)
+}
+
+/// Returns whether the subscriber existed. Tokens, memberships, tags and delivery tasks are
+/// removed by the foreign key cascades.
+async fn remove_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool> {
+    let n_deleted = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id,)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(n_deleted > 0)
+}
@@ -0,0 +1,284 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Editor, RequireRole, Role},
+    configuration::SubscriptionSettings,
+    domain::{SubscriberStatus, TagName},
+    tags::{
+        FilterParameters, InvalidFilter, TagAction, add_tags, bulk_tag, delete_tag, remove_tag,
+    },
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct TagFormData {
+    /// Comma separated
+    tags: String,
+}
+
+/// Every field is optional, empty ones are ignored
+#[derive(Deserialize)]
+pub struct BulkTagFormData {
+    action: TagAction,
+    tags: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    list: String,
+    #[serde(default)]
+    segment: String,
+    #[serde(default)]
+    include_tags: String,
+    #[serde(default)]
+    exclude_tags: String,
+}
+
+struct TagRow {
+    name: String,
+    subscribers: i64,
+}
+
+/// Every tag with the number of subscribers having it
+#[instrument(name = "Listing tags", skip_all)]
+pub async fn list_tags(
+    role: web::ReqData<Role>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let tags = match get_tags(&pool).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            error!("Failed to list tags: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let is_editor = *role >= Role::Editor;
+    let rows_html: String = tags
+        .iter()
+        .map(|t| {
+            let name = htmlescape::encode_minimal(&t.name);
+            let delete_html = match is_editor {
+                true => format!(
+                    r#"<form action="/admin/tags/{name}/delete" method="post"><input type="submit" value="Delete"></form>"#
+                ),
+                false => String::new(),
+            };
+            format!(
+                "    <tr><td>{name}</td><td>{}</td><td>{delete_html}</td></tr>\n",
+                t.subscribers,
+            )
+        })
+        .collect();
+    let status_options: String = SubscriberStatus::ALL
+        .iter()
+        .map(|s| format!(r#"<option value="{s}">{s}</option>"#))
+        .collect();
+    let form_html = match is_editor {
+        true => format!(
+            r#"<h2>Bulk tagging</h2>
+<p>Adds or removes tags on every subscriber matching all the given conditions, leave a condition
+empty to ignore it. Tags are separated by commas.</p>
+<form action="/admin/tags/bulk" method="post">
+    <label>Action <select name="action"><option value="add">Add</option><option value="remove">Remove</option></select></label>
+    <label>Tags <input type="text" name="tags" placeholder="beta, conference-2026"></label>
+    <label>Status <select name="status"><option value="">any</option>{status_options}</select></label>
+    <label>List <input type="text" name="list" placeholder="newsletter"></label>
+    <label>Segment <input type="text" name="segment" placeholder="signed_up_after 2026-01-01"></label>
+    <label>Tagged with any of <input type="text" name="include_tags"></label>
+    <label>Tagged with none of <input type="text" name="exclude_tags"></label>
+    <button type="submit">Apply</button>
+</form>
+"#
+        ),
+        false => String::new(),
+    };
+
+    html_page(
+        "Tags",
+        &format!(
+            r#"{}<h1>Tags</h1>
+<p>Issues can be limited to subscribers with or without some tags through the
+<code>include_tags</code> and <code>exclude_tags</code> fields of the request body.</p>
+<table>
+    <tr><th>Tag</th><th>Subscribers</th><th></th></tr>
+{rows_html}</table>
+{form_html}<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+        ),
+    )
+}
+
+#[instrument(name = "Bulk tagging subscribers", skip_all, fields(action = ?form.action))]
+pub async fn bulk_tag_action(
+    _: RequireRole<Editor>,
+    form: web::Form<BulkTagFormData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let tags = match TagName::parse_all(&split_tags(&form.tags)) {
+        Ok(tags) if tags.is_empty() => {
+            FlashMessage::error("Enter at least one tag.").send();
+            return see_other("/admin/tags");
+        }
+        Ok(tags) => tags,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return see_other("/admin/tags");
+        }
+    };
+    let status = match non_empty(form.status)
+        .map(|s| SubscriberStatus::parse(&s))
+        .transpose()
+    {
+        Ok(status) => status,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return see_other("/admin/tags");
+        }
+    };
+    let parameters = FilterParameters {
+        status,
+        list: non_empty(form.list),
+        segment: non_empty(form.segment),
+        include_tags: split_tags(&form.include_tags),
+        exclude_tags: split_tags(&form.exclude_tags),
+    };
+    let filter = match parameters.into_filter(&pool, &settings.attributes).await {
+        Ok(filter) => filter,
+        Err(InvalidFilter::Unexpected(e)) => {
+            error!("Failed to build the subscriber filter: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+        Err(e) => {
+            FlashMessage::error(format!("{}.", e)).send();
+            return see_other("/admin/tags");
+        }
+    };
+
+    match bulk_tag(&pool, form.action, &tags, &filter).await {
+        Ok(n_changed) => {
+            info!(n_changed, "Bulk tagged subscribers");
+            let verb = match form.action {
+                TagAction::Add => "added",
+                TagAction::Remove => "removed",
+            };
+            FlashMessage::info(format!("{} tags have been {}.", n_changed, verb)).send();
+        }
+        Err(e) => {
+            error!("Failed to bulk tag subscribers: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other("/admin/tags")
+}
+
+/// Removes the tag from every subscriber
+#[instrument(name = "Deleting a tag", skip(pool))]
+pub async fn delete_tag_action(
+    _: RequireRole<Editor>,
+    tag: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let Ok(tag) = TagName::parse(&tag) else {
+        return HttpResponse::NotFound().finish();
+    };
+    match delete_tag(&pool, &tag).await {
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Ok(true) => {
+            FlashMessage::info(format!("{} has been deleted.", tag)).send();
+            see_other("/admin/tags")
+        }
+        Err(e) => {
+            error!("Failed to delete tag: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[instrument(name = "Tagging a subscriber", skip(form, pool))]
+pub async fn tag_subscriber(
+    _: RequireRole<Editor>,
+    subscriber_id: web::Path<Uuid>,
+    form: web::Form<TagFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let subscriber_url = format!("/admin/subscribers/{}", subscriber_id);
+    let tags = match TagName::parse_all(&split_tags(&form.tags)) {
+        Ok(tags) if tags.is_empty() => {
+            FlashMessage::error("Enter at least one tag.").send();
+            return see_other(&subscriber_url);
+        }
+        Ok(tags) => tags,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return see_other(&subscriber_url);
+        }
+    };
+    match add_tags(&pool, *subscriber_id, &tags).await {
+        Ok(false) => return HttpResponse::NotFound().finish(),
+        Ok(true) => FlashMessage::info("The tags have been added.").send(),
+        Err(e) => {
+            error!("Failed to tag subscriber: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other(&subscriber_url)
+}
+
+#[instrument(name = "Untagging a subscriber", skip(pool))]
+pub async fn untag_subscriber(
+    _: RequireRole<Editor>,
+    path: web::Path<(Uuid, String)>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let (subscriber_id, tag) = path.into_inner();
+    let Ok(tag) = TagName::parse(&tag) else {
+        return HttpResponse::NotFound().finish();
+    };
+    match remove_tag(&pool, subscriber_id, &tag).await {
+        Ok(false) => return HttpResponse::NotFound().finish(),
+        Ok(true) => FlashMessage::info(format!("{} has been removed.", tag)).send(),
+        Err(e) => {
+            error!("Failed to untag subscriber: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other(&format!("/admin/subscribers/{}", subscriber_id))
+}
+
+fn split_tags(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+async fn get_tags(pool: &PgPool) -> Result<Vec<TagRow>> {
+    sqlx::query_as!(
+        TagRow,
+        r#"
+        SELECT t.name, COUNT(st.subscriber_id) AS "subscribers!"
+        FROM tags t
+        LEFT JOIN subscriber_tags st ON st.tag_id = t.tag_id
+        GROUP BY t.tag_id
+        ORDER BY t.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve tags")
+}
@@ -0,0 +1,230 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+
+use crate::{
+    authentication::{
+        AuthError, Credentials, TotpState, UserId, disable_totp, enable_totp, get_totp_state,
+        provisioning_uri, qr_code_svg, regenerate_recovery_codes, start_totp_enrollment,
+        validate_credentials,
+    },
+    routes::get_username,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct EnableTwoFactorFormData {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableTwoFactorFormData {
+    password: Secret<String>,
+}
+
+#[instrument(
+    name = "Rendering the two-factor settings",
+    skip(user_id, pool, flash_messages),
+    fields(user_id = %*user_id)
+)]
+pub async fn two_factor_settings(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let user_id = **user_id;
+    let totp_state = match get_totp_state(&pool, user_id).await {
+        Ok(totp_state) => totp_state,
+        Err(e) => {
+            error!("Failed to retrieve the TOTP state: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let content = match totp_state {
+        TotpState::Disabled => r#"<p>Two-factor authentication is off.</p>
+<form action="/admin/two-factor/enroll" method="post">
+    <button type="submit">Set up two-factor authentication</button>
+</form>"#
+            .to_string(),
+        TotpState::Pending { secret } => {
+            let enrollment = get_username(user_id, &pool).await.and_then(|username| {
+                let uri = provisioning_uri(&secret, &username)?;
+                Ok((qr_code_svg(&uri)?, uri))
+            });
+            let (qr_code, uri) = match enrollment {
+                Ok(enrollment) => enrollment,
+                Err(e) => {
+                    error!("Failed to build the provisioning URI: {:?}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            };
+            format!(
+                r#"<p>Scan this QR code with your authenticator app, then enter the code it shows.</p>
+{qr_code}
+<p>Can't scan it? Use this link: <code>{}</code><br>or enter this key manually: <code>{secret}</code></p>
+<form action="/admin/two-factor/enable" method="post">
+    <label>Authentication code
+        <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" name="code">
+    </label>
+    <button type="submit">Enable two-factor authentication</button>
+</form>"#,
+                htmlescape::encode_minimal(&uri),
+            )
+        }
+        TotpState::Enabled => r#"<p>Two-factor authentication is on.</p>
+<form action="/admin/two-factor/recovery-codes" method="post">
+    <button type="submit">Generate new recovery codes</button>
+</form>
+<form action="/admin/two-factor/disable" method="post">
+    <label>Password
+        <input type="password" placeholder="Enter your password" name="password">
+    </label>
+    <button type="submit">Disable two-factor authentication</button>
+</form>"#
+            .to_string(),
+    };
+
+    html_page(
+        "Two-factor authentication",
+        &format!(
+            r#"{}{content}
+<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages)
+        ),
+    )
+}
+
+#[instrument(name = "Starting TOTP enrollment", skip(user_id, pool), fields(user_id = %*user_id))]
+pub async fn enroll_two_factor(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if let Err(e) = start_totp_enrollment(&pool, **user_id).await {
+        error!("Failed to start TOTP enrollment: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    see_other("/admin/two-factor")
+}
+
+/// Verifies the first code from the authenticator app and shows the recovery codes, this is the
+/// only time they are visible
+#[instrument(
+    name = "Enabling TOTP",
+    skip(form, user_id, pool),
+    fields(user_id = %*user_id)
+)]
+pub async fn enable_two_factor(
+    form: web::Form<EnableTwoFactorFormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match enable_totp(&pool, **user_id, form.code.trim()).await {
+        Ok(Some(recovery_codes)) => {
+            info!("Enabled two-factor authentication");
+            recovery_codes_page(&recovery_codes)
+        }
+        Ok(None) => {
+            FlashMessage::error("Invalid authentication code.").send();
+            see_other("/admin/two-factor")
+        }
+        Err(e) => {
+            error!("Failed to enable TOTP: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[instrument(
+    name = "Regenerating recovery codes",
+    skip(user_id, pool),
+    fields(user_id = %*user_id)
+)]
+pub async fn regenerate_two_factor_recovery_codes(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match get_totp_state(&pool, **user_id).await {
+        Ok(TotpState::Enabled) => {}
+        Ok(_) => {
+            FlashMessage::error("Two-factor authentication is not enabled.").send();
+            return see_other("/admin/two-factor");
+        }
+        Err(e) => {
+            error!("Failed to retrieve the TOTP state: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    match regenerate_recovery_codes(&pool, **user_id).await {
+        Ok(recovery_codes) => recovery_codes_page(&recovery_codes),
+        Err(e) => {
+            error!("Failed to regenerate recovery codes: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Turns the second factor off, asking for the password again so that an unattended session
+/// isn't enough
+#[instrument(
+    name = "Disabling TOTP",
+    skip(form, user_id, pool),
+    fields(user_id = %*user_id)
+)]
+pub async fn disable_two_factor(
+    form: web::Form<DisableTwoFactorFormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = **user_id;
+    let username = match get_username(user_id, &pool).await {
+        Ok(username) => username,
+        Err(e) => {
+            error!("Failed to load the logged in user: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let credentials = Credentials {
+        username,
+        password: form.0.password,
+    };
+    match validate_credentials(credentials, &pool).await {
+        Ok(_) => {}
+        Err(AuthError::InvalidCredentials(_)) => {
+            FlashMessage::error("The password is incorrect.").send();
+            return see_other("/admin/two-factor");
+        }
+        Err(AuthError::UnexpectedError(e)) => {
+            error!("Failed to validate credentials: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    if let Err(e) = disable_totp(&pool, user_id).await {
+        error!("Failed to disable TOTP: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    info!("Disabled two-factor authentication");
+    FlashMessage::info("Two-factor authentication has been disabled.").send();
+    see_other("/admin/two-factor")
+}
+
+fn recovery_codes_page(recovery_codes: &[String]) -> HttpResponse {
+    let codes_html: String = recovery_codes
+        .iter()
+        .map(|code| format!("    <li><code>{code}</code></li>\n"))
+        .collect();
+    html_page(
+        "Recovery codes",
+        &format!(
+            r#"<p>Two-factor authentication is on. Store these recovery codes somewhere safe, each
+of them can be used once to log in without your authenticator app. They won't be shown again.</p>
+<ul>
+{codes_html}</ul>
+<p><a href="/admin/two-factor">Done</a></p>"#
+        ),
+    )
+}
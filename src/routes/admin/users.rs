@@ -0,0 +1,233 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::{Context, Result};
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{Owner, RequireRole, Role, UserId, create_user, validate_new_password},
+    domain::SubscriberEmail,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct CreateUserFormData {
+    username: String,
+    email: String,
+    role: Role,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleFormData {
+    role: Role,
+}
+
+struct UserRow {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: String,
+    totp_enabled: bool,
+}
+
+#[instrument(name = "Listing users", skip_all)]
+pub async fn list_users(
+    _: RequireRole<Owner>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    let users = match get_users(&pool).await {
+        Ok(users) => users,
+        Err(e) => {
+            error!("Failed to list users: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let role_options = |selected: &str| -> String {
+        Role::ALL
+            .iter()
+            .map(|r| {
+                let selected = if r.as_str() == selected {
+                    " selected"
+                } else {
+                    ""
+                };
+                format!(r#"<option value="{r}"{selected}>{r}</option>"#)
+            })
+            .collect()
+    };
+    let rows_html: String = users
+        .iter()
+        .map(|u| {
+            format!(
+                r#"    <tr>
+        <td>{}</td><td>{}</td><td>{}</td>
+        <td><form action="/admin/users/{id}/role" method="post"><select name="role">{}</select><input type="submit" value="Change"></form></td>
+        <td><form action="/admin/users/{id}/delete" method="post"><input type="submit" value="Delete"></form></td>
+    </tr>
+"#,
+                htmlescape::encode_minimal(&u.username),
+                htmlescape::encode_minimal(u.email.as_deref().unwrap_or("-")),
+                if u.totp_enabled { "on" } else { "off" },
+                role_options(&u.role),
+                id = u.user_id,
+            )
+        })
+        .collect();
+
+    html_page(
+        "Users",
+        &format!(
+            r#"{}<h1>Users</h1>
+<table>
+    <tr><th>Username</th><th>Email</th><th>Two-factor</th><th>Role</th><th></th></tr>
+{rows_html}</table>
+<h2>Add a user</h2>
+<form action="/admin/users" method="post">
+    <label>Username <input type="text" name="username"></label>
+    <label>Email <input type="email" name="email"></label>
+    <label>Role <select name="role">{}</select></label>
+    <label>Password <input type="password" name="password"></label>
+    <label>Confirm password <input type="password" name="password_check"></label>
+    <button type="submit">Add user</button>
+</form>
+<p><a href="/admin/dashboard">&lt;- Back</a></p>"#,
+            flash_messages_html(&flash_messages),
+            role_options(Role::Viewer.as_str()),
+        ),
+    )
+}
+
+#[instrument(name = "Creating a user", skip_all, fields(username = %form.username, role = %form.role))]
+pub async fn add_user(
+    _: RequireRole<Owner>,
+    form: web::Form<CreateUserFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let username = form.username.trim();
+    if username.is_empty() {
+        FlashMessage::error("The username can't be empty.").send();
+        return see_other("/admin/users");
+    }
+    let email = match form.email.trim() {
+        "" => None,
+        email => match SubscriberEmail::parse(email.to_string()) {
+            Ok(email) => Some(email),
+            Err(e) => {
+                FlashMessage::error(e.to_string()).send();
+                return see_other("/admin/users");
+            }
+        },
+    };
+    if let Err(e) = validate_new_password(&form.password, &form.password_check) {
+        FlashMessage::error(e.to_string()).send();
+        return see_other("/admin/users");
+    }
+
+    match create_user(
+        username,
+        form.password,
+        email.as_ref().map(AsRef::as_ref),
+        form.role,
+        &pool,
+    )
+    .await
+    {
+        Ok(Some(_)) => {
+            info!("Created user");
+            FlashMessage::info(format!("{} has been added.", username)).send();
+        }
+        Ok(None) => FlashMessage::error("That username or email is already taken.").send(),
+        Err(e) => {
+            error!("Failed to create user: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    see_other("/admin/users")
+}
+
+/// Owners can't change their own role, so that there's always at least one owner left
+#[instrument(name = "Changing a user's role", skip(current_user_id, form, pool), fields(role = %form.role))]
+pub async fn change_user_role(
+    _: RequireRole<Owner>,
+    current_user_id: web::ReqData<UserId>,
+    user_id: web::Path<Uuid>,
+    form: web::Form<ChangeRoleFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if *user_id == **current_user_id {
+        FlashMessage::error("You can't change your own role.").send();
+        return see_other("/admin/users");
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET role = $2 WHERE user_id = $1"#,
+        *user_id,
+        form.role.as_str(),
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            FlashMessage::info("The role has been changed.").send();
+            see_other("/admin/users")
+        }
+        Err(e) => {
+            error!("Failed to execute query: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Their sessions end on the next request, the middleware doesn't let unknown users through
+#[instrument(name = "Deleting a user", skip(current_user_id, pool))]
+pub async fn delete_user(
+    _: RequireRole<Owner>,
+    current_user_id: web::ReqData<UserId>,
+    user_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if *user_id == **current_user_id {
+        FlashMessage::error("You can't delete yourself.").send();
+        return see_other("/admin/users");
+    }
+
+    let result = sqlx::query!(r#"DELETE FROM users WHERE user_id = $1"#, *user_id)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            FlashMessage::info("The user has been deleted.").send();
+            see_other("/admin/users")
+        }
+        Err(e) => {
+            error!("Failed to execute query: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_users(pool: &PgPool) -> Result<Vec<UserRow>> {
+    sqlx::query_as!(
+        UserRow,
+        r#"
+        SELECT user_id, username, email, role, totp_enabled
+        FROM users
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users")
+}
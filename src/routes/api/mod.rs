@@ -0,0 +1,7 @@
+pub mod stats;
+pub mod subscribers;
+pub mod tags;
+
+pub use stats::*;
+pub use subscribers::*;
+pub use tags::*;
@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use actix_web::{HttpResponse, ResponseError, web};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use crate::{
+    authentication::{RequireScope, StatsRead},
+    problem_details::{ProblemDetails, error_chain_fmt},
+    routes::count_subscribers_by_status,
+};
+
+#[derive(Serialize)]
+struct StatsResponse {
+    /// Number of subscribers by status, keyed by the status name
+    subscribers: BTreeMap<&'static str, i64>,
+}
+
+#[instrument(name = "Reading stats", skip_all, fields(api_key_id = %auth.api_key.api_key_id))]
+pub async fn get_stats(
+    auth: RequireScope<StatsRead>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, StatsError> {
+    let counts = count_subscribers_by_status(&pool).await?;
+    Ok(HttpResponse::Ok().json(StatsResponse {
+        subscribers: counts
+            .into_iter()
+            .map(|(status, count)| (status.as_str(), count))
+            .collect(),
+    }))
+}
+
+#[derive(thiserror::Error)]
+pub enum StatsError {
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for StatsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for StatsError {
+    fn error_response(&self) -> HttpResponse {
+        ProblemDetails::new(self.status_code(), "internal_error").into_response()
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    ApplicationBaseUrl,
+    authentication::{RequireScope, SubscribersWrite},
+    configuration::SubscriptionSettings,
+    domain::{NewSubscriber, SubscriberStatus},
+    email_client::EmailClient,
+    routes::{SubscribeError, check_email_domain, find_list, register_subscriber},
+};
+
+/// Missing fields are reported like invalid ones, instead of failing deserialization
+#[derive(Deserialize, Debug)]
+pub struct CreateSubscriberBody {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub email: String,
+    /// Slug of the list to join, the default list if missing
+    #[serde(default)]
+    pub list: Option<String>,
+    /// Custom attributes, see [`SubscriptionSettings::attributes`]
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct SubscriberResponse {
+    id: Uuid,
+    list: String,
+    status: SubscriberStatus,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// JSON counterpart of the subscription form, for API clients. The subscriber still has to
+/// confirm through the emailed link. Addresses already on the list get a 200 with their current
+/// state instead of a 201.
+#[instrument(
+    name = "Creating a subscriber through the API",
+    skip(auth, body, pool, email_client, base_url, settings),
+    fields(
+        api_key_id = %auth.api_key.api_key_id,
+        subscriber_email = %body.email,
+        subscriber_name = %body.name,
+        list = ?body.list
+    )
+)]
+pub async fn create_subscriber(
+    auth: RequireScope<SubscribersWrite>,
+    body: web::Json<CreateSubscriberBody>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, SubscribeError> {
+    let body = body.into_inner();
+    // typed JSON values are checked against the attribute kinds like form values
+    let attributes = body.attributes.into_iter().map(|(name, value)| {
+        let value = match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(s) => s,
+            value => value.to_string(),
+        };
+        (name, value)
+    });
+    let subscriber =
+        NewSubscriber::with_attributes(body.name, body.email, &settings.attributes, attributes)?;
+    check_email_domain(&pool, &settings, &subscriber).await?;
+    let list = find_list(&pool, body.list.as_deref()).await?;
+    let subscription =
+        register_subscriber(&pool, &email_client, &base_url.0, &list, subscriber).await?;
+    let mut response = match subscription.created {
+        true => HttpResponse::Created(),
+        false => HttpResponse::Ok(),
+    };
+    Ok(response.json(SubscriberResponse {
+        id: subscription.id,
+        list: subscription.list,
+        status: subscription.status,
+        subscribed_at: subscription.subscribed_at,
+    }))
+}
@@ -0,0 +1,163 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{RequireScope, SubscribersWrite},
+    configuration::SubscriptionSettings,
+    domain::{SegmentError, TagName, TagNameError},
+    problem_details::{ProblemDetails, error_chain_fmt},
+    tags::{
+        FilterParameters, InvalidFilter, TagAction, add_tags, bulk_tag, get_subscriber_tags,
+        remove_tag,
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct TagsBody {
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BulkTagBody {
+    pub action: TagAction,
+    pub tags: Vec<String>,
+    /// Subscribers to apply the action to, everyone if missing
+    #[serde(default)]
+    pub filter: FilterParameters,
+}
+
+#[derive(Serialize)]
+struct TagsResponse {
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BulkTagResponse {
+    /// Number of tag assignments that were added or removed
+    affected: u64,
+}
+
+/// Tags that don't exist yet are created, tags the subscriber already has are left alone
+#[instrument(
+    name = "Tagging a subscriber through the API",
+    skip(auth, body, pool),
+    fields(api_key_id = %auth.api_key.api_key_id)
+)]
+pub async fn add_subscriber_tags(
+    auth: RequireScope<SubscribersWrite>,
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<TagsBody>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TagError> {
+    let subscriber_id = subscriber_id.into_inner();
+    let tags = TagName::parse_all(&body.tags)?;
+    if !add_tags(&pool, subscriber_id, &tags).await? {
+        return Err(TagError::UnknownSubscriber);
+    }
+    Ok(HttpResponse::Ok().json(TagsResponse {
+        tags: get_subscriber_tags(&pool, subscriber_id).await?,
+    }))
+}
+
+#[instrument(
+    name = "Untagging a subscriber through the API",
+    skip(auth, pool),
+    fields(api_key_id = %auth.api_key.api_key_id)
+)]
+pub async fn remove_subscriber_tag(
+    auth: RequireScope<SubscribersWrite>,
+    path: web::Path<(Uuid, String)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TagError> {
+    let (subscriber_id, tag) = path.into_inner();
+    let tag = TagName::parse(&tag)?;
+    if !remove_tag(&pool, subscriber_id, &tag).await? {
+        return Err(TagError::UnknownSubscriber);
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[instrument(
+    name = "Bulk tagging subscribers through the API",
+    skip(auth, body, pool, settings),
+    fields(api_key_id = %auth.api_key.api_key_id)
+)]
+pub async fn bulk_tag_subscribers(
+    auth: RequireScope<SubscribersWrite>,
+    body: web::Json<BulkTagBody>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, TagError> {
+    let body = body.into_inner();
+    let tags = TagName::parse_all(&body.tags)?;
+    let filter = body.filter.into_filter(&pool, &settings.attributes).await?;
+    let affected = bulk_tag(&pool, body.action, &tags, &filter).await?;
+    Ok(HttpResponse::Ok().json(BulkTagResponse { affected }))
+}
+
+#[derive(thiserror::Error)]
+pub enum TagError {
+    #[error(transparent)]
+    InvalidTag(#[from] TagNameError),
+    #[error("There is no subscriber with this id")]
+    UnknownSubscriber,
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error(transparent)]
+    InvalidSegment(#[from] SegmentError),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl From<InvalidFilter> for TagError {
+    fn from(e: InvalidFilter) -> Self {
+        match e {
+            InvalidFilter::UnknownList(list) => TagError::UnknownList(list),
+            InvalidFilter::Segment(e) => TagError::InvalidSegment(e),
+            InvalidFilter::Tag(e) => TagError::InvalidTag(e),
+            InvalidFilter::Unexpected(e) => TagError::Unexpected(e),
+        }
+    }
+}
+
+impl std::fmt::Debug for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TagError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TagError::InvalidTag(_) | TagError::UnknownList(_) | TagError::InvalidSegment(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            TagError::UnknownSubscriber => StatusCode::NOT_FOUND,
+            TagError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            TagError::InvalidTag(e) => ProblemDetails::new(status, e.code())
+                .detail(e.to_string())
+                .into_response(),
+            TagError::UnknownSubscriber => ProblemDetails::new(status, "unknown_subscriber")
+                .detail(self.to_string())
+                .into_response(),
+            TagError::UnknownList(_) => ProblemDetails::new(status, "unknown_list")
+                .detail(self.to_string())
+                .into_response(),
+            TagError::InvalidSegment(e) => ProblemDetails::new(status, "invalid_segment")
+                .detail(e.to_string())
+                .into_response(),
+            TagError::Unexpected(_) => {
+                ProblemDetails::new(status, "internal_error").into_response()
+            }
+        }
+    }
+}
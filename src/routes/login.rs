@@ -0,0 +1,94 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{Span, error, instrument, warn};
+
+use crate::{
+    authentication::{AuthError, Credentials, TotpState, get_totp_state, validate_credentials},
+    session::TypedSession,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct LoginFormData {
+    username: String,
+    password: Secret<String>,
+}
+
+pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    html_page(
+        "Login",
+        &format!(
+            r#"{}
+<form action="/login" method="post">
+    <label>Username
+        <input type="text" placeholder="Enter Username" name="username">
+    </label>
+    <label>Password
+        <input type="password" placeholder="Enter Password" name="password">
+    </label>
+    <button type="submit">Login</button>
+</form>
+<p><a href="/password/forgot">Forgot your password?</a></p>"#,
+            flash_messages_html(&flash_messages)
+        ),
+    )
+}
+
+/// Starts a new session for the user on success, bounces back to the login form with a flash
+/// message otherwise. Users with a second factor are sent on to the second login step.
+#[instrument(
+    name = "Logging in",
+    skip(form, pool, session),
+    fields(username = %form.username, user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    form: web::Form<LoginFormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> HttpResponse {
+    let credentials = Credentials {
+        username: form.0.username,
+        password: form.0.password,
+    };
+
+    let user_id = match validate_credentials(credentials, &pool).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials(e)) => {
+            warn!("Rejecting login with invalid credentials: {}", e);
+            FlashMessage::error("Authentication failed").send();
+            return see_other("/login");
+        }
+        Err(AuthError::UnexpectedError(e)) => {
+            error!("Failed to validate credentials: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let totp_state = match get_totp_state(&pool, user_id).await {
+        Ok(totp_state) => totp_state,
+        Err(e) => {
+            error!("Failed to check for a second factor: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    // new key on login, a session id planted before authenticating is worthless
+    session.renew();
+    if let TotpState::Enabled = totp_state {
+        if let Err(e) = session.insert_pending_user_id(user_id) {
+            error!("Failed to store the user id in the session: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+        return see_other("/login/two-factor");
+    }
+    if let Err(e) = session.insert_user_id(user_id) {
+        error!("Failed to store the user id in the session: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    see_other("/admin/dashboard")
+}
@@ -0,0 +1,96 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    authentication::{SECOND_FACTOR_LOCKOUT, SecondFactorOutcome, verify_second_factor},
+    session::TypedSession,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct TwoFactorFormData {
+    code: String,
+}
+
+pub async fn two_factor_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    match session.get_pending_user_id() {
+        Ok(Some(_)) => {}
+        Ok(None) => return see_other("/login"),
+        Err(e) => {
+            error!("Failed to read the session: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    html_page(
+        "Two-factor authentication",
+        &format!(
+            r#"{}
+<form action="/login/two-factor" method="post">
+    <label>Authentication code
+        <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" name="code">
+    </label>
+    <button type="submit">Verify</button>
+</form>
+<p>Lost your device? Enter one of your recovery codes instead.</p>"#,
+            flash_messages_html(&flash_messages)
+        ),
+    )
+}
+
+/// Second login step, checks a TOTP or recovery code for the user whose password was accepted
+#[instrument(
+    name = "Verifying the second factor",
+    skip(form, session, pool),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn two_factor(
+    form: web::Form<TwoFactorFormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = match session.get_pending_user_id() {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return see_other("/login"),
+        Err(e) => {
+            error!("Failed to read the session: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    match verify_second_factor(&pool, user_id, &form.code).await {
+        Ok(SecondFactorOutcome::Accepted) => {}
+        Ok(SecondFactorOutcome::Rejected) => {
+            warn!("Rejecting an invalid second factor");
+            FlashMessage::error("Invalid authentication code.").send();
+            return see_other("/login/two-factor");
+        }
+        Ok(SecondFactorOutcome::LockedOut) => {
+            warn!("Rejecting a second factor while locked out");
+            session.log_out();
+            FlashMessage::error(format!(
+                "Too many failed attempts, please try again in {} minutes.",
+                SECOND_FACTOR_LOCKOUT.num_minutes()
+            ))
+            .send();
+            return see_other("/login");
+        }
+        Err(e) => {
+            error!("Failed to verify the second factor: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    if let Err(e) = session.complete_second_factor(user_id) {
+        error!("Failed to store the user id in the session: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    see_other("/admin/dashboard")
+}
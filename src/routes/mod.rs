@@ -1,5 +1,20 @@
+pub mod admin;
+pub mod api;
 pub mod health_check;
+pub mod login;
+pub mod login_two_factor;
+pub mod newsletters;
+pub mod password_reset;
 pub mod subscriptions;
+pub mod subscriptions_confirm;
+pub mod subscriptions_unsubscribe;
 
+pub use admin::*;
 pub use health_check::*;
+pub use login::*;
+pub use login_two_factor::*;
+pub use newsletters::*;
+pub use password_reset::*;
 pub use subscriptions::*;
+pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
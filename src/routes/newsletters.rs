@@ -0,0 +1,301 @@
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{Span, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        ApiAuthError, AuthError, NewslettersPublish, Role, TotpState, authorize_api_key,
+        basic_authentication, bearer_token, get_role, get_totp_state, validate_credentials,
+    },
+    configuration::SubscriptionSettings,
+    domain::{
+        DEFAULT_LIST_SLUG, MailingList, Segment, SegmentError, SubscriberFilter, TagName,
+        TagNameError, bind_values,
+    },
+    idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
+    problem_details::{ProblemDetails, error_chain_fmt},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct BodyData {
+    pub title: String,
+    pub content: Content,
+    /// Slug of the list to publish to, the default list if missing
+    #[serde(default)]
+    pub list: Option<String>,
+    /// Only members matching the segment get the issue, see [`Segment`]
+    #[serde(default)]
+    pub segment: Option<String>,
+    /// Only members with at least one of these tags get the issue
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    /// Members with any of these tags don't get the issue
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Content {
+    pub html: String,
+    pub text: String,
+}
+
+/// Stores the issue and queues one delivery per confirmed member of the target list, the default
+/// list unless the body names one, optionally narrowed down to a segment and by tags; the emails
+/// themselves are sent by the issue delivery worker.
+/// Requests carrying an `Idempotency-Key` header are only processed once per user, retries get
+/// the saved response back. Only publishers and owners may publish, either with their
+/// credentials or with an API key carrying the `newsletters:publish` scope.
+#[instrument(
+    name = "Publishing a newsletter issue",
+    skip(request, body, pool, settings),
+    fields(
+        title = %body.title,
+        list = ?body.list,
+        segment = ?body.segment,
+        username = tracing::field::Empty,
+        api_key_id = tracing::field::Empty,
+        user_id = tracing::field::Empty
+    )
+)]
+pub async fn publish_newsletter(
+    request: HttpRequest,
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate_publisher(&request, &pool).await?;
+    Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let list = MailingList::find(&pool, body.list.as_deref())
+        .await?
+        .ok_or_else(|| {
+            PublishError::UnknownList(body.list.as_deref().unwrap_or(DEFAULT_LIST_SLUG).into())
+        })?;
+    let filter = SubscriberFilter {
+        segment: body
+            .segment
+            .as_deref()
+            .map(|segment| Segment::parse(segment, &settings.attributes))
+            .transpose()?,
+        include_tags: TagName::parse_all(&body.include_tags)?,
+        exclude_tags: TagName::parse_all(&body.exclude_tags)?,
+        ..Default::default()
+    };
+
+    let idempotency_key =
+        IdempotencyKey::from_request(&request).map_err(PublishError::InvalidIdempotencyKey)?;
+
+    let mut transaction = match &idempotency_key {
+        Some(idempotency_key) => match try_processing(&pool, idempotency_key, user_id).await? {
+            NextAction::StartProcessing(transaction) => transaction,
+            NextAction::ReturnSavedResponse(saved_response) => {
+                info!("Returning saved response for a repeated request");
+                return Ok(saved_response);
+            }
+        },
+        None => pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?,
+    };
+
+    let issue_id = insert_newsletter_issue(&mut transaction, &body, list.list_id)
+        .await
+        .context("Failed to store newsletter issue details")?;
+    enqueue_delivery_tasks(&mut transaction, issue_id, &filter)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    let response = HttpResponse::Accepted().finish();
+    let response = match &idempotency_key {
+        Some(idempotency_key) => {
+            save_response(transaction, idempotency_key, user_id, response).await?
+        }
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to publish a newsletter issue")?;
+            response
+        }
+    };
+    info!("Newsletter issue queued for delivery");
+    Ok(response)
+}
+
+#[derive(thiserror::Error)]
+pub enum PublishError {
+    #[error("Authentication failed")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    ApiKey(#[from] ApiAuthError),
+    #[error("Basic authentication is refused for users with two-factor authentication")]
+    TwoFactorRequired,
+    #[error("The user lacks the publisher role")]
+    MissingRole,
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error(transparent)]
+    InvalidSegment(#[from] SegmentError),
+    #[error(transparent)]
+    InvalidTag(#[from] TagNameError),
+    #[error("{0}")]
+    InvalidIdempotencyKey(#[source] anyhow::Error),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PublishError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PublishError::InvalidCredentials(_) | PublishError::TwoFactorRequired => {
+                StatusCode::UNAUTHORIZED
+            }
+            PublishError::ApiKey(e) => e.status_code(),
+            PublishError::MissingRole => StatusCode::FORBIDDEN,
+            PublishError::UnknownList(_)
+            | PublishError::InvalidSegment(_)
+            | PublishError::InvalidTag(_)
+            | PublishError::InvalidIdempotencyKey(_) => StatusCode::BAD_REQUEST,
+            PublishError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            PublishError::InvalidCredentials(_) => {
+                let problem = ProblemDetails::new(status, "invalid_credentials")
+                    .detail("Authenticate with valid Basic credentials or a Bearer API key.");
+                problem
+                    .response_builder()
+                    .insert_header(("WWW-Authenticate", r#"Basic realm="publish""#))
+                    .json(&problem)
+            }
+            PublishError::ApiKey(e) => e.error_response(),
+            PublishError::TwoFactorRequired => ProblemDetails::new(status, "two_factor_required")
+                .detail("Accounts with two-factor authentication must publish with an API key.")
+                .into_response(),
+            PublishError::MissingRole => ProblemDetails::new(status, "insufficient_role")
+                .detail("Only publishers and owners can publish newsletters.")
+                .into_response(),
+            PublishError::UnknownList(_) => ProblemDetails::new(status, "unknown_list")
+                .detail(self.to_string())
+                .into_response(),
+            PublishError::InvalidSegment(e) => ProblemDetails::new(status, "invalid_segment")
+                .detail(e.to_string())
+                .into_response(),
+            PublishError::InvalidTag(e) => ProblemDetails::new(status, e.code())
+                .detail(e.to_string())
+                .into_response(),
+            PublishError::InvalidIdempotencyKey(e) => {
+                ProblemDetails::new(status, "invalid_idempotency_key")
+                    .detail(e.to_string())
+                    .into_response()
+            }
+            PublishError::Unexpected(_) => {
+                ProblemDetails::new(status, "internal_error").into_response()
+            }
+        }
+    }
+}
+
+/// Returns the user the issue is published on behalf of: the user behind the credentials for
+/// Basic authentication, the creator of the key for Bearer authentication. Basic authentication
+/// can't carry a second factor, so it is refused for users who enabled one.
+async fn authenticate_publisher(
+    request: &HttpRequest,
+    pool: &PgPool,
+) -> Result<Uuid, PublishError> {
+    match bearer_token(request.headers()) {
+        Ok(key) => {
+            let api_key = authorize_api_key::<NewslettersPublish>(pool, key).await?;
+            Span::current().record("api_key_id", tracing::field::display(&api_key.api_key_id));
+            Ok(api_key.user_id)
+        }
+        Err(_) => {
+            let credentials = basic_authentication(request.headers())
+                .map_err(PublishError::InvalidCredentials)?;
+            Span::current().record("username", tracing::field::display(&credentials.username));
+            let user_id = validate_credentials(credentials, pool)
+                .await
+                .map_err(|e| match e {
+                    AuthError::InvalidCredentials(e) => PublishError::InvalidCredentials(e),
+                    AuthError::UnexpectedError(e) => PublishError::Unexpected(e),
+                })?;
+            if let TotpState::Enabled = get_totp_state(pool, user_id).await? {
+                return Err(PublishError::TwoFactorRequired);
+            }
+            match get_role(user_id, pool).await? {
+                Some(role) if role >= Role::Publisher => Ok(user_id),
+                _ => Err(PublishError::MissingRole),
+            }
+        }
+    }
+}
+
+#[instrument(name = "Inserting a newsletter issue", skip(transaction, body))]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    body: &BodyData,
+    list_id: Uuid,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, list_id,
+            segment
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        newsletter_issue_id,
+        body.title,
+        body.content.text,
+        body.content.html,
+        Utc::now(),
+        list_id,
+        body.segment,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// The filter is compiled to a parameterized condition, so this query can't be checked at
+/// compile time
+#[instrument(name = "Enqueueing delivery tasks", skip(transaction, filter))]
+async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    filter: &SubscriberFilter,
+) -> Result<(), sqlx::Error> {
+    let (condition, params) = filter.to_sql(2);
+    let sql = format!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_id)
+        SELECT i.newsletter_issue_id, m.subscriber_id
+        FROM newsletter_issues i
+        JOIN list_memberships m ON m.list_id = i.list_id
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE i.newsletter_issue_id = $1 AND m.status = 'confirmed' AND {condition}
+        "#
+    );
+    bind_values(sqlx::query(&sql).bind(newsletter_issue_id), params)
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
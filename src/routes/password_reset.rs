@@ -0,0 +1,244 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{Instrument, error, info, instrument, warn};
+
+use crate::{
+    ApplicationBaseUrl,
+    authentication::{
+        PASSWORD_RESET_TOKEN_TTL, change_password, check_password_reset_token,
+        consume_password_reset_token, create_password_reset_token, validate_new_password,
+    },
+    domain::SubscriberEmail,
+    email_client::{EmailClient, SendEmailError},
+    session::delete_user_sessions,
+    utils::{flash_messages_html, html_page, see_other},
+};
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordFormData {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordParameters {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+pub async fn forgot_password_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    html_page(
+        "Forgot password",
+        &format!(
+            r#"{}
+<form action="/password/forgot" method="post">
+    <label>Email
+        <input type="email" placeholder="Enter the email of your account" name="email">
+    </label>
+    <button type="submit">Send reset link</button>
+</form>
+<p><a href="/login">&lt;- Back to login</a></p>"#,
+            flash_messages_html(&flash_messages)
+        ),
+    )
+}
+
+/// Emails a reset link if an account uses that address. The lookup and the email happen in the
+/// background and the response is the same either way, so neither its content nor its timing
+/// tell which addresses have an account.
+#[instrument(
+    name = "Requesting a password reset",
+    skip(form, pool, email_client, base_url)
+)]
+pub async fn request_password_reset(
+    form: web::Form<ForgotPasswordFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> HttpResponse {
+    let email = form.into_inner().email;
+    tokio::spawn(
+        async move {
+            if let Err(e) =
+                send_password_reset_link(&pool, &email_client, &base_url.0, email.trim()).await
+            {
+                error!("Failed to send a password reset link: {:?}", e);
+            }
+        }
+        .in_current_span(),
+    );
+
+    FlashMessage::info(
+        "If an account with that email address exists, a link to reset its password has been sent.",
+    )
+    .send();
+    see_other("/password/forgot")
+}
+
+pub async fn reset_password_form(
+    parameters: web::Query<ResetPasswordParameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
+    match check_password_reset_token(&pool, &parameters.token).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return invalid_reset_link(),
+        Err(e) => {
+            error!("Failed to check password reset token: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    html_page(
+        "Reset password",
+        &format!(
+            r#"{}
+<form action="/password/reset" method="post">
+    <input type="hidden" name="token" value="{}">
+    <label>New password
+        <input type="password" placeholder="Enter new password" name="new_password">
+    </label>
+    <br>
+    <label>Confirm new password
+        <input type="password" placeholder="Type the new password again" name="new_password_check">
+    </label>
+    <br>
+    <button type="submit">Reset password</button>
+</form>"#,
+            flash_messages_html(&flash_messages),
+            htmlescape::encode_attribute(&parameters.token),
+        ),
+    )
+}
+
+#[instrument(name = "Resetting a password", skip(form, pool))]
+pub async fn reset_password(
+    form: web::Form<ResetPasswordFormData>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    // tokens we issue are alphanumeric, anything else can't be valid and shouldn't end up in a
+    // redirect
+    if !form.token.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return invalid_reset_link();
+    }
+    if let Err(e) = validate_new_password(&form.new_password, &form.new_password_check) {
+        FlashMessage::error(e.to_string()).send();
+        return see_other(&format!("/password/reset?token={}", form.token));
+    }
+
+    let mut transaction = match pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            error!(
+                "Failed to acquire a Postgres connection from the pool: {:?}",
+                e
+            );
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let user_id = match consume_password_reset_token(&mut transaction, &form.token).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return invalid_reset_link(),
+        Err(e) => {
+            error!("Failed to consume password reset token: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if let Err(e) = change_password(user_id, form.0.new_password, &mut *transaction).await {
+        error!("Failed to change password: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    // whoever knew the old password is logged out
+    if let Err(e) = delete_user_sessions(user_id, &mut *transaction).await {
+        error!("Failed to log the user out: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    if let Err(e) = transaction.commit().await {
+        error!(
+            "Failed to commit SQL transaction to reset a password: {:?}",
+            e
+        );
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    FlashMessage::info("Your password has been reset, you can now log in.").send();
+    see_other("/login")
+}
+
+fn invalid_reset_link() -> HttpResponse {
+    FlashMessage::error("The password reset link is invalid or has expired.").send();
+    see_other("/password/forgot")
+}
+
+/// Creates a reset token for the user with that email, if any, and emails them the link. Like
+/// for subscribers, emails that only differ by case are the same address.
+#[instrument(
+    name = "Sending a password reset link",
+    skip(pool, email_client, base_url, email)
+)]
+async fn send_password_reset_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    email: &str,
+) -> anyhow::Result<()> {
+    let user = sqlx::query!(
+        r#"SELECT user_id, email AS "email!" FROM users WHERE lower(email) = lower($1)"#,
+        email,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the user")?;
+    let Some(user) = user else {
+        warn!("Password reset requested for an unknown email");
+        return Ok(());
+    };
+
+    let email =
+        SubscriberEmail::parse(user.email).context("The stored email of a user is invalid")?;
+    let token = create_password_reset_token(pool, user.user_id)
+        .await
+        .context("Failed to create password reset token")?;
+    send_password_reset_email(email_client, &email, base_url, &token).await?;
+    info!("Sent a password reset link");
+    Ok(())
+}
+
+#[instrument(
+    name = "Sending a password reset email",
+    skip(email_client, email, base_url, token)
+)]
+async fn send_password_reset_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), SendEmailError> {
+    let reset_link = format!("{}/password/reset?token={}", base_url, token);
+    let valid_for = PASSWORD_RESET_TOKEN_TTL.num_minutes();
+    let html_body = format!(
+        "Someone asked to reset the password of your account.<br />\
+        Click <a href=\"{}\">here</a> to choose a new one, the link is valid for {} minutes.<br />\
+        If it wasn't you, you can ignore this email.",
+        reset_link, valid_for
+    );
+    let plain_body = format!(
+        "Someone asked to reset the password of your account.\n\
+        Visit {} to choose a new one, the link is valid for {} minutes.\n\
+        If it wasn't you, you can ignore this email.",
+        reset_link, valid_for
+    );
+
+    email_client
+        .send_email(email, "Reset your password", &html_body, &plain_body)
+        .await
+}
@@ -1,66 +1,472 @@
-use actix_web::{HttpResponse, web};
-use chrono::Utc;
+use std::collections::HashMap;
+
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::{Rng, distr::Alphanumeric};
 use serde::Deserialize;
-use sqlx::PgPool;
-use tracing::{error, info, instrument};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{info, instrument};
 use uuid::Uuid;
 
-use crate::domain::NewSubscriber;
+use crate::{
+    ApplicationBaseUrl,
+    configuration::SubscriptionSettings,
+    domain::{
+        DEFAULT_LIST_SLUG, DomainPolicy, FieldError, InvalidNewSubscriber, MailingList,
+        NewSubscriber, SubscriberStatus,
+    },
+    email_client::{EmailClient, SendEmailError},
+    problem_details::{ProblemDetails, error_chain_fmt},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct FormData {
     pub name: String,
     pub email: String,
+    /// Slug of the list to join, the default list if missing
+    #[serde(default)]
+    pub list: Option<String>,
+    /// Other fields, the ones named after a configured attribute are stored with the subscriber
+    #[serde(flatten)]
+    pub attributes: HashMap<String, String>,
 }
 
 #[instrument(
     name = "Adding a new subscriber",
-    skip(pool, form)
+    skip(pool, form, email_client, base_url, settings)
     fields(
         subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_name = %form.name,
+        list = ?form.list
     )
 )]
-pub async fn subscribe(form: web::Form<FormData>, pool: web::Data<PgPool>) -> HttpResponse {
-    let subscriber = match form.0.try_into() {
-        Ok(info) => info,
-        Err(e) => {
-            error!("Failed to parse subscriber info: {}", e);
-            return HttpResponse::BadRequest().finish();
+pub async fn subscribe(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, SubscribeError> {
+    let form = form.into_inner();
+    let attributes = form
+        .attributes
+        .into_iter()
+        .filter(|(name, _)| settings.attributes.iter().any(|a| &a.name == name));
+    let subscriber =
+        NewSubscriber::with_attributes(form.name, form.email, &settings.attributes, attributes)?;
+    check_email_domain(&pool, &settings, &subscriber).await?;
+    let list = find_list(&pool, form.list.as_deref()).await?;
+    register_subscriber(&pool, &email_client, &base_url.0, &list, subscriber).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Shared by the form and the JSON endpoints
+#[derive(thiserror::Error)]
+pub enum SubscribeError {
+    #[error("Invalid subscriber details: {0}")]
+    Validation(#[from] InvalidNewSubscriber),
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscribeError::Validation(_) | SubscribeError::UnknownList(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscribeError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
-    };
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            SubscribeError::Validation(e) => ProblemDetails::new(status, "validation_failed")
+                .detail("One or more fields are invalid.")
+                .errors(e.0.clone())
+                .into_response(),
+            SubscribeError::UnknownList(_) => ProblemDetails::new(status, "unknown_list")
+                .detail(self.to_string())
+                .into_response(),
+            SubscribeError::Unexpected(_) => {
+                ProblemDetails::new(status, "internal_error").into_response()
+            }
+        }
+    }
+}
+
+/// Applies the domain policy, rejected domains are reported as an invalid email field
+pub async fn check_email_domain(
+    pool: &PgPool,
+    settings: &SubscriptionSettings,
+    subscriber: &NewSubscriber,
+) -> Result<(), SubscribeError> {
+    DomainPolicy::load(pool, settings.block_disposable_domains)
+        .await?
+        .check(&subscriber.email)
+        .map_err(|e| InvalidNewSubscriber(vec![FieldError::email(e)]))?;
+    Ok(())
+}
+
+/// Shared by the form and the JSON endpoints
+pub async fn find_list(pool: &PgPool, slug: Option<&str>) -> Result<MailingList, SubscribeError> {
+    MailingList::find(pool, slug)
+        .await?
+        .ok_or_else(|| SubscribeError::UnknownList(slug.unwrap_or(DEFAULT_LIST_SLUG).to_string()))
+}
+
+/// A subscriber's membership of a list as stored after signing up
+pub struct Subscription {
+    pub id: Uuid,
+    pub list: String,
+    pub status: SubscriberStatus,
+    pub subscribed_at: DateTime<Utc>,
+    /// `false` when the address was already on the list
+    pub created: bool,
+}
+
+/// Stores a validated subscriber pending confirmation on `list` and emails them the
+/// confirmation link, shared by the form and the JSON endpoints.
+///
+/// Signing up again with a known address is not an error: pending members get the same
+/// confirmation link again, confirmed ones are left alone and unsubscribed ones start a fresh
+/// opt-in. Each list is opted into separately.
+pub async fn register_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    list: &MailingList,
+    subscriber: NewSubscriber,
+) -> anyhow::Result<Subscription> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
 
     info!("Saving new subscriber details in DB");
-    match insert_subscriber(&pool, &subscriber).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        // ignoring the error as it is already logged in insert_subscriber
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    let subscriber_id = match insert_subscriber(&mut transaction, &subscriber)
+        .await
+        .context("Failed to insert the new subscriber")?
+    {
+        Some(subscriber_id) => subscriber_id,
+        None => find_existing_subscriber(&mut transaction, &subscriber).await?,
+    };
+    let (subscription, subscription_token) =
+        join_list(&mut transaction, subscriber_id, list).await?;
+    refresh_subscriber_status(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to update the subscriber status")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscriber")?;
+
+    if let Some(subscription_token) = subscription_token {
+        send_confirmation_email(
+            email_client,
+            subscriber,
+            list,
+            base_url,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send the confirmation email")?;
     }
+
+    Ok(subscription)
+}
+
+/// Attributes given on a repeated signup are ignored: nothing proves the address belongs to
+/// whoever filled in the form, so they must not be able to change what's stored about it
+#[instrument(name = "Finding an existing subscriber", skip_all)]
+async fn find_existing_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &NewSubscriber,
+) -> anyhow::Result<Uuid> {
+    let existing = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        subscriber.email.as_ref(),
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to retrieve the existing subscriber")?;
+    Ok(existing.id)
 }
 
-#[instrument(name = "Inserting a new subscriber", skip(pool, subscriber))]
-async fn insert_subscriber(pool: &PgPool, subscriber: &NewSubscriber) -> Result<(), sqlx::Error> {
-    match sqlx::query!(
+/// Returns the token to send a confirmation link for, if any
+#[instrument(name = "Joining a list", skip(transaction, list), fields(list = %list.slug))]
+async fn join_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list: &MailingList,
+) -> anyhow::Result<(Subscription, Option<String>)> {
+    let existing = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, $5)
+        SELECT status, subscribed_at
+        FROM list_memberships
+        WHERE list_id = $1 AND subscriber_id = $2
+        FOR UPDATE
         "#,
-        Uuid::new_v4(),
-        subscriber.email.as_ref(),
-        subscriber.name.as_ref(),
-        Utc::now(),
-        "confirmed",
+        list.list_id,
+        subscriber_id,
     )
-    .execute(pool)
+    .fetch_optional(&mut **transaction)
     .await
-    {
-        Ok(_) => {
-            info!("Successfully saved customer details");
-            Ok(())
+    .context("Failed to retrieve the list membership")?;
+    let mut subscription = Subscription {
+        id: subscriber_id,
+        list: list.slug.clone(),
+        status: SubscriberStatus::PendingConfirmation,
+        subscribed_at: Utc::now(),
+        created: existing.is_none(),
+    };
+
+    let Some(existing) = existing else {
+        sqlx::query!(
+            r#"
+            INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+            VALUES ($1, $2, 'pending_confirmation', $3)
+            "#,
+            list.list_id,
+            subscriber_id,
+            subscription.subscribed_at,
+        )
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to insert the list membership")?;
+        let subscription_token = generate_token();
+        store_token(
+            transaction,
+            subscriber_id,
+            list.list_id,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to store the confirmation token")?;
+        return Ok((subscription, Some(subscription_token)));
+    };
+    subscription.status = SubscriberStatus::parse(&existing.status)?;
+    subscription.subscribed_at = existing.subscribed_at;
+
+    let subscription_token = match subscription.status {
+        SubscriberStatus::PendingConfirmation => {
+            info!("Resending the confirmation link to a pending subscriber");
+            let existing_token = sqlx::query!(
+                r#"
+                SELECT subscription_token
+                FROM subscription_tokens
+                WHERE subscriber_id = $1 AND list_id = $2
+                LIMIT 1
+                "#,
+                subscriber_id,
+                list.list_id,
+            )
+            .fetch_optional(&mut **transaction)
+            .await
+            .context("Failed to retrieve the confirmation token")?;
+            match existing_token {
+                Some(row) => Some(row.subscription_token),
+                None => {
+                    let subscription_token = generate_token();
+                    store_token(
+                        transaction,
+                        subscriber_id,
+                        list.list_id,
+                        &subscription_token,
+                    )
+                    .await
+                    .context("Failed to store the confirmation token")?;
+                    Some(subscription_token)
+                }
+            }
         }
-        Err(e) => {
-            error!("Failed to execute query: {:?}", e);
-            Err(e)
+        SubscriberStatus::Confirmed => {
+            info!("Ignoring a signup from a confirmed subscriber");
+            None
         }
+        SubscriberStatus::Unsubscribed => {
+            info!("Restarting the opt-in of an unsubscribed subscriber");
+            subscription.status = SubscriberStatus::PendingConfirmation;
+            subscription.subscribed_at = Utc::now();
+            sqlx::query!(
+                r#"
+                UPDATE list_memberships
+                SET status = 'pending_confirmation', subscribed_at = $3, unsubscribed_at = NULL
+                WHERE list_id = $1 AND subscriber_id = $2
+                "#,
+                list.list_id,
+                subscriber_id,
+                subscription.subscribed_at,
+            )
+            .execute(&mut **transaction)
+            .await
+            .context("Failed to restart the opt-in")?;
+            // links from the previous opt-in must not confirm the new one
+            sqlx::query!(
+                r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2"#,
+                subscriber_id,
+                list.list_id,
+            )
+            .execute(&mut **transaction)
+            .await
+            .context("Failed to delete old confirmation tokens")?;
+            let subscription_token = generate_token();
+            store_token(
+                transaction,
+                subscriber_id,
+                list.list_id,
+                &subscription_token,
+            )
+            .await
+            .context("Failed to store the confirmation token")?;
+            Some(subscription_token)
+        }
+    };
+    Ok((subscription, subscription_token))
+}
+
+/// Keeps `subscriptions.status` in line with the list memberships: confirmed if any membership
+/// is, pending if any is, unsubscribed once the subscriber left every list
+#[instrument(name = "Refreshing the subscriber status", skip(transaction))]
+pub async fn refresh_subscriber_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = summary.status,
+            unsubscribed_at = CASE
+                WHEN summary.status = 'unsubscribed'
+                THEN COALESCE(subscriptions.unsubscribed_at, $2)
+            END
+        FROM (
+            SELECT CASE
+                WHEN bool_or(status = 'confirmed') THEN 'confirmed'
+                WHEN bool_or(status = 'pending_confirmation') THEN 'pending_confirmation'
+                ELSE 'unsubscribed'
+            END AS status
+            FROM list_memberships
+            WHERE subscriber_id = $1
+            HAVING COUNT(*) > 0
+        ) AS summary
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        Utc::now(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(
+    name = "Sending a confirmation email to a new subscriber",
+    skip(email_client, subscriber, list, base_url, subscription_token)
+)]
+async fn send_confirmation_email(
+    email_client: &EmailClient,
+    subscriber: NewSubscriber,
+    list: &MailingList,
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<(), SendEmailError> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    );
+    let html_body = format!(
+        "Welcome to {}!<br />\
+        Click <a href=\"{}\">here</a> to confirm your subscription.",
+        htmlescape::encode_minimal(&list.name),
+        confirmation_link
+    );
+    let plain_body = format!(
+        "Welcome to {}!\nVisit {} to confirm your subscription.",
+        list.name, confirmation_link
+    );
+
+    email_client
+        .send_email(&subscriber.email, "Welcome!", &html_body, &plain_body)
+        .await
+}
+
+/// Returns `None` if the address is already known
+#[instrument(name = "Inserting a new subscriber", skip(transaction, subscriber))]
+async fn insert_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &NewSubscriber,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let subscriber_id = Uuid::new_v4();
+    let n_inserted = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, unsubscribe_token, attributes
+        )
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5, $6)
+        ON CONFLICT (lower(email)) DO NOTHING
+        "#,
+        subscriber_id,
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+        Utc::now(),
+        generate_token(),
+        subscriber.attributes.to_json(),
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+    if n_inserted == 0 {
+        return Ok(None);
     }
+
+    info!("Successfully saved customer details");
+    Ok(Some(subscriber_id))
+}
+
+#[instrument(
+    name = "Storing subscription token in the database",
+    skip(transaction, subscription_token)
+)]
+async fn store_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        VALUES ($1, $2, $3)
+        "#,
+        subscription_token,
+        subscriber_id,
+        list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Generate a random 25-characters-long case-sensitive token, used for subscription and
+/// unsubscribe links
+pub fn generate_token() -> String {
+    let mut rng = rand::rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
 }
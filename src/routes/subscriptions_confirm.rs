@@ -0,0 +1,114 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    problem_details::{ProblemDetails, error_chain_fmt},
+    routes::refresh_subscriber_status,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct Parameters {
+    pub subscription_token: String,
+}
+
+#[instrument(name = "Confirming a pending subscriber", skip(parameters, pool))]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ConfirmError> {
+    let (subscriber_id, list_id) = get_membership_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to look up the subscription token")?
+        .ok_or(ConfirmError::UnknownToken)?;
+    confirm_membership(&pool, subscriber_id, list_id)
+        .await
+        .context("Failed to mark the subscriber as confirmed")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("There is no subscriber associated with the provided token")]
+    UnknownToken,
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmError::UnknownToken => StatusCode::UNAUTHORIZED,
+            ConfirmError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            ConfirmError::UnknownToken => ProblemDetails::new(status, "unknown_token")
+                .detail("The confirmation link is invalid.")
+                .into_response(),
+            ConfirmError::Unexpected(_) => {
+                ProblemDetails::new(status, "internal_error").into_response()
+            }
+        }
+    }
+}
+
+/// Only pending memberships are confirmed, an old link doesn't bring back someone who left the
+/// list since
+#[instrument(name = "Marking subscriber as confirmed", skip(pool))]
+async fn confirm_membership(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'confirmed'
+        WHERE subscriber_id = $1 AND list_id = $2 AND status = 'pending_confirmation'
+        "#,
+        subscriber_id,
+        list_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    refresh_subscriber_status(&mut transaction, subscriber_id).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[instrument(
+    name = "Getting the list membership from token",
+    skip(pool, subscription_token)
+)]
+async fn get_membership_from_token(
+    pool: &PgPool,
+    subscription_token: &str,
+) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT subscriber_id, list_id
+        FROM subscription_tokens
+        WHERE subscription_token = $1
+        "#,
+        subscription_token,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| (r.subscriber_id, r.list_id)))
+}
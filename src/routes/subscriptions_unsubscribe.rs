@@ -0,0 +1,222 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    domain::MailingList,
+    problem_details::{ProblemDetails, error_chain_fmt},
+    routes::refresh_subscriber_status,
+    utils::html_page,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct UnsubscribeParameters {
+    pub unsubscribe_token: String,
+    /// Slug of the list to leave, every list if missing
+    pub list: Option<String>,
+}
+
+/// Builds the link included in every list email, for both the body and `List-Unsubscribe`. It
+/// only removes the subscriber from the list the email was sent to.
+pub fn unsubscribe_link(base_url: &str, unsubscribe_token: &str, list_slug: &str) -> String {
+    format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}&list={}",
+        base_url, unsubscribe_token, list_slug
+    )
+}
+
+/// Landing page of the link clicked by the subscriber, which only asks for confirmation: mail
+/// scanners and link prefetchers follow `GET` links, so they must not change anything
+#[instrument(name = "Confirming an unsubscription", skip(parameters, pool), fields(list = ?parameters.list))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let list = find_list(&pool, parameters.list.as_deref()).await?;
+    if !subscriber_exists(&pool, &parameters.unsubscribe_token)
+        .await
+        .context("Failed to look up the unsubscribe token")?
+    {
+        return Err(UnsubscribeError::UnknownToken);
+    }
+
+    let (question, action) = match &list {
+        Some(list) => (
+            format!("Stop receiving {}?", htmlescape::encode_minimal(&list.name)),
+            format!(
+                "/subscriptions/unsubscribe?unsubscribe_token={}&list={}",
+                parameters.unsubscribe_token, list.slug
+            ),
+        ),
+        None => (
+            "Stop receiving all our emails?".to_string(),
+            format!(
+                "/subscriptions/unsubscribe?unsubscribe_token={}",
+                parameters.unsubscribe_token
+            ),
+        ),
+    };
+    Ok(html_page(
+        "Unsubscribe",
+        &format!(
+            r#"<p>{}</p>
+<form action="{}" method="post">
+    <button type="submit">Unsubscribe</button>
+</form>"#,
+            question,
+            htmlescape::encode_minimal(&action)
+        ),
+    ))
+}
+
+/// Submitted by the confirmation page, and the RFC 8058 one-click request sent by mail clients,
+/// which carries the token in the URL as well
+#[instrument(name = "Unsubscribing a subscriber", skip(parameters, pool), fields(list = ?parameters.list))]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let list = find_list(&pool, parameters.list.as_deref()).await?;
+    mark_subscriber_as_unsubscribed(
+        &pool,
+        &parameters.unsubscribe_token,
+        list.map(|list| list.list_id),
+    )
+    .await
+    .context("Failed to mark the subscriber as unsubscribed")?
+    .ok_or(UnsubscribeError::UnknownToken)?;
+    Ok(html_page(
+        "Unsubscribed",
+        "<p>You have been unsubscribed.</p>",
+    ))
+}
+
+/// The list to leave, `None` for every list
+async fn find_list(
+    pool: &PgPool,
+    slug: Option<&str>,
+) -> Result<Option<MailingList>, UnsubscribeError> {
+    match slug {
+        Some(slug) => Ok(Some(
+            MailingList::find(pool, Some(slug))
+                .await?
+                .ok_or_else(|| UnsubscribeError::UnknownList(slug.to_string()))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("There is no subscriber associated with the provided token")]
+    UnknownToken,
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::UnknownToken => StatusCode::UNAUTHORIZED,
+            UnsubscribeError::UnknownList(_) => StatusCode::BAD_REQUEST,
+            UnsubscribeError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        match self {
+            UnsubscribeError::UnknownToken => ProblemDetails::new(status, "unknown_token")
+                .detail("The unsubscribe link is invalid.")
+                .into_response(),
+            UnsubscribeError::UnknownList(_) => ProblemDetails::new(status, "unknown_list")
+                .detail(self.to_string())
+                .into_response(),
+            UnsubscribeError::Unexpected(_) => {
+                ProblemDetails::new(status, "internal_error").into_response()
+            }
+        }
+    }
+}
+
+#[instrument(
+    name = "Looking up an unsubscribe token",
+    skip(pool, unsubscribe_token)
+)]
+async fn subscriber_exists(pool: &PgPool, unsubscribe_token: &str) -> Result<bool, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1"#,
+        unsubscribe_token,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber.is_some())
+}
+
+/// Leaves the given list, or every list when `list_id` is `None`
+#[instrument(
+    name = "Marking subscriber as unsubscribed",
+    skip(pool, unsubscribe_token)
+)]
+async fn mark_subscriber_as_unsubscribed(
+    pool: &PgPool,
+    unsubscribe_token: &str,
+    list_id: Option<Uuid>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let Some(subscriber) = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1 FOR UPDATE"#,
+        unsubscribe_token,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    // keep the original timestamp if the link is followed more than once
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'unsubscribed', unsubscribed_at = COALESCE(unsubscribed_at, $3)
+        WHERE subscriber_id = $1 AND ($2::uuid IS NULL OR list_id = $2)
+        "#,
+        subscriber.id,
+        list_id,
+        now,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    match list_id {
+        Some(_) => refresh_subscriber_status(&mut transaction, subscriber.id).await?,
+        None => {
+            sqlx::query!(
+                r#"
+                UPDATE subscriptions
+                SET status = 'unsubscribed', unsubscribed_at = COALESCE(unsubscribed_at, $2)
+                WHERE id = $1
+                "#,
+                subscriber.id,
+                now,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(Some(subscriber.id))
+}
@@ -0,0 +1,80 @@
+mod store;
+
+pub use store::PostgresSessionStore;
+
+use std::future::{Ready, ready};
+
+use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
+use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use anyhow::Context;
+use uuid::Uuid;
+
+/// Typed wrapper around [`Session`], so that handlers don't deal with raw keys
+pub struct TypedSession(Session);
+
+impl TypedSession {
+    const USER_ID_KEY: &'static str = "user_id";
+    const PENDING_USER_ID_KEY: &'static str = "pending_second_factor_user_id";
+
+    /// Rotates the session key, call it whenever the privilege level changes (e.g. on login)
+    pub fn renew(&self) {
+        self.0.renew();
+    }
+
+    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::USER_ID_KEY, user_id)
+    }
+
+    pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::USER_ID_KEY)
+    }
+
+    /// Password checked, but the second factor hasn't been verified yet. Such sessions don't get
+    /// past the admin middleware.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    /// Promotes a pending session to a fully logged in one
+    pub fn complete_second_factor(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.renew();
+        self.insert_user_id(user_id)
+    }
+
+    pub fn log_out(self) {
+        self.0.purge()
+    }
+}
+
+/// Ends every session of the user, logged in or waiting for the second factor, e.g. once their
+/// password was reset. [`Session`] stores values JSON-encoded, hence the match on the encoded id.
+pub async fn delete_user_sessions<'c>(
+    user_id: Uuid,
+    executor: impl sqlx::PgExecutor<'c>,
+) -> anyhow::Result<()> {
+    let user_id = serde_json::to_string(&user_id)?;
+    sqlx::query!(
+        r#"DELETE FROM sessions WHERE state ->> $1 = $3 OR state ->> $2 = $3"#,
+        TypedSession::USER_ID_KEY,
+        TypedSession::PENDING_USER_ID_KEY,
+        user_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to delete the user's sessions")?;
+    Ok(())
+}
+
+impl FromRequest for TypedSession {
+    type Error = <Session as FromRequest>::Error;
+    type Future = Ready<Result<TypedSession, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(TypedSession(req.get_session())))
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rand::{Rng, distr::Alphanumeric};
+use sqlx::PgPool;
+
+type SessionState = HashMap<String, String>;
+
+/// Keeps session state in the `sessions` table, expired rows are ignored when loading and
+/// cleaned up whenever a new session is created
+#[derive(Clone)]
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SessionStore for PostgresSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT state
+            FROM sessions
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load session state")
+        .map_err(LoadError::Other)?;
+
+        row.map(|row| serde_json::from_value(row.state))
+            .transpose()
+            .context("Failed to deserialize session state")
+            .map_err(LoadError::Deserialization)
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let state = serde_json::to_value(session_state)
+            .context("Failed to serialize session state")
+            .map_err(SaveError::Serialization)?;
+        let session_key = generate_session_key();
+
+        sqlx::query!(r#"DELETE FROM sessions WHERE expires_at <= now()"#,)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clean up expired sessions")
+            .map_err(SaveError::Other)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (session_key, state, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            session_key.as_ref(),
+            state,
+            expires_at(ttl),
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to save session state")
+        .map_err(SaveError::Other)?;
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_value(&session_state)
+            .context("Failed to serialize session state")
+            .map_err(UpdateError::Serialization)?;
+
+        let n_updated_rows = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET state = $2, expires_at = $3
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key.as_ref(),
+            state,
+            expires_at(ttl),
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update session state")
+        .map_err(UpdateError::Other)?
+        .rows_affected();
+
+        match n_updated_rows {
+            // the session expired in the meantime, start a new one
+            0 => self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            }),
+            _ => Ok(session_key),
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE sessions SET expires_at = $2 WHERE session_key = $1"#,
+            session_key.as_ref(),
+            expires_at(ttl),
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update session TTL")?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_key = $1"#,
+            session_key.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete session")?;
+        Ok(())
+    }
+}
+
+fn expires_at(ttl: &Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+/// 64 random alphanumeric characters, same as the stores bundled with actix-session
+fn generate_session_key() -> SessionKey {
+    let value: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect();
+
+    // the key is well within the size limit enforced by `SessionKey`
+    value.try_into().expect("Generated an invalid session key")
+}
@@ -0,0 +1,199 @@
+use actix_web::web::Bytes;
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Utc};
+use csv_async::AsyncWriterBuilder;
+use futures_util::{Stream, StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, types::Json};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Rows fetched from the cursor at a time, which is all that is held in memory
+pub const EXPORT_BATCH_SIZE: usize = 1000;
+
+const CSV_HEADER: [&str; 9] = [
+    "id",
+    "email",
+    "name",
+    "status",
+    "subscribed_at",
+    "unsubscribed_at",
+    "lists",
+    "tags",
+    "attributes",
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One row per subscriber, lists and tags are joined with `;` and attributes kept as JSON.
+    /// Cells a spreadsheet would read as a formula are prefixed with `'`.
+    #[default]
+    Csv,
+    /// One JSON object per line, a.k.a. JSON Lines
+    #[serde(alias = "jsonl")]
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedMembership {
+    list: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+    lists: Json<Vec<ExportedMembership>>,
+    tags: Vec<String>,
+    attributes: serde_json::Value,
+}
+
+/// Every subscriber, oldest first, read through a server-side cursor so memory use doesn't
+/// depend on the number of subscribers. The cursor's transaction is rolled back if the stream is
+/// dropped early, e.g. when the client disconnects.
+#[instrument(name = "Exporting subscribers", skip(pool))]
+pub async fn export_subscribers(
+    pool: &PgPool,
+    format: ExportFormat,
+) -> anyhow::Result<impl Stream<Item = anyhow::Result<Bytes>> + 'static> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // cursors can't be prepared, so this isn't checked at compile time
+    sqlx::query(
+        r#"
+        DECLARE subscriber_export NO SCROLL CURSOR FOR
+        SELECT
+            s.id, s.email, s.name, s.status, s.subscribed_at, s.unsubscribed_at,
+            COALESCE(
+                (
+                    SELECT json_agg(json_build_object(
+                        'list', l.slug,
+                        'status', m.status,
+                        'subscribed_at', m.subscribed_at,
+                        'unsubscribed_at', m.unsubscribed_at
+                    ) ORDER BY l.slug)
+                    FROM list_memberships m
+                    JOIN lists l ON l.list_id = m.list_id
+                    WHERE m.subscriber_id = s.id
+                ),
+                '[]'
+            ) AS lists,
+            ARRAY(
+                SELECT t.name
+                FROM subscriber_tags st
+                JOIN tags t ON t.tag_id = st.tag_id
+                WHERE st.subscriber_id = s.id
+                ORDER BY t.name
+            ) AS tags,
+            s.attributes
+        FROM subscriptions s
+        ORDER BY s.subscribed_at, s.id
+        "#,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to declare the export cursor")?;
+
+    let header = match format {
+        ExportFormat::Csv => Some(write_csv(&[], true).await),
+        ExportFormat::Ndjson => None,
+    };
+    let batches = stream::try_unfold(Some(transaction), move |transaction| async move {
+        let Some(mut transaction) = transaction else {
+            return Ok(None);
+        };
+        let subscribers: Vec<ExportedSubscriber> =
+            sqlx::query_as(&format!("FETCH {EXPORT_BATCH_SIZE} FROM subscriber_export"))
+                .fetch_all(&mut *transaction)
+                .await
+                .context("Failed to fetch from the export cursor")?;
+        if subscribers.is_empty() {
+            transaction.commit().await?;
+            return Ok(None);
+        }
+        let chunk = match format {
+            ExportFormat::Csv => write_csv(&subscribers, false).await?,
+            ExportFormat::Ndjson => write_ndjson(&subscribers)?,
+        };
+        Ok(Some((chunk, Some(transaction))))
+    });
+    Ok(stream::iter(header).chain(batches))
+}
+
+async fn write_csv(subscribers: &[ExportedSubscriber], header: bool) -> anyhow::Result<Bytes> {
+    let mut writer = AsyncWriterBuilder::new()
+        .has_headers(false)
+        .create_writer(Vec::new());
+    if header {
+        writer.write_record(CSV_HEADER).await?;
+    }
+    for s in subscribers {
+        let lists: Vec<String> = s
+            .lists
+            .iter()
+            .map(|m| format!("{}:{}", m.list, m.status))
+            .collect();
+        writer
+            .write_record([
+                s.id.to_string(),
+                escape_formula(&s.email),
+                escape_formula(&s.name),
+                s.status.clone(),
+                s.subscribed_at.to_rfc3339(),
+                s.unsubscribed_at.map_or(String::new(), |t| t.to_rfc3339()),
+                lists.join(";"),
+                escape_formula(&s.tags.join(";")),
+                escape_formula(&s.attributes.to_string()),
+            ])
+            .await?;
+    }
+    let bytes = writer
+        .into_inner()
+        .await
+        .map_err(|e| anyhow!("Failed to write the exported subscribers: {}", e))?;
+    Ok(bytes.into())
+}
+
+/// Names and emails come from public signups or uploaded files, spreadsheets would run a cell like
+/// `=HYPERLINK(...)` as a formula when a CSV with it is opened. Prefixing it with `'` keeps it text.
+pub(crate) fn escape_formula(cell: &str) -> String {
+    match cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{cell}"),
+        false => cell.to_string(),
+    }
+}
+
+fn write_ndjson(subscribers: &[ExportedSubscriber]) -> anyhow::Result<Bytes> {
+    let mut bytes = Vec::new();
+    for s in subscribers {
+        serde_json::to_writer(&mut bytes, s)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes.into())
+}
@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, anyhow};
+use chrono::Utc;
+use csv_async::{AsyncReaderBuilder, AsyncWriter, StringRecord};
+use sqlx::PgPool;
+use tokio::io::AsyncRead;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    configuration::SubscriptionSettings,
+    domain::{
+        AttributeDefinition, DomainPolicy, FieldError, InvalidNewSubscriber, MailingList,
+        NewSubscriber, SubscriberStatus,
+    },
+    routes::generate_token,
+    subscriber_export::escape_formula,
+};
+
+/// Valid rows are upserted in transactions of this many rows
+pub const IMPORT_BATCH_SIZE: usize = 500;
+
+/// A row that wasn't imported, with the reason as shown to someone signing up
+#[derive(Debug)]
+pub struct RejectedRow {
+    /// Line number in the file, the header being line 1
+    pub line: u64,
+    pub email: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Subscribers that didn't exist yet
+    pub n_created: u64,
+    /// Subscribers that already existed, their custom attributes are merged
+    pub n_updated: u64,
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl ImportSummary {
+    /// The rejected rows as CSV, with a `line`, `email` and `reason` column. The email and reason
+    /// echo the uploaded file, so they are escaped like in exports.
+    pub async fn rejected_csv(&self) -> anyhow::Result<String> {
+        let mut writer = AsyncWriter::from_writer(Vec::new());
+        writer.write_record(["line", "email", "reason"]).await?;
+        for row in &self.rejected {
+            writer
+                .write_record([
+                    row.line.to_string(),
+                    escape_formula(&row.email),
+                    escape_formula(&row.reason),
+                ])
+                .await?;
+        }
+        let bytes = writer
+            .into_inner()
+            .await
+            .map_err(|e| anyhow!("Failed to write the rejected rows: {}", e))?;
+        String::from_utf8(bytes).context("The rejected rows are not valid UTF-8")
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("The file has no email column")]
+    MissingEmailColumn,
+    #[error("The file is not valid CSV: {0}")]
+    InvalidCsv(#[source] csv_async::Error),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+/// Which column holds which field, matched case-insensitively against the header row. Columns
+/// named after a custom attribute are imported as that attribute, other columns are ignored.
+#[derive(Debug, PartialEq)]
+struct Columns {
+    email: usize,
+    name: Option<usize>,
+    /// Used when there is no name column, as exported by Mailchimp
+    first_name: Option<usize>,
+    last_name: Option<usize>,
+    attributes: Vec<(String, usize)>,
+}
+
+impl Columns {
+    fn from_headers(
+        headers: &StringRecord,
+        definitions: &[AttributeDefinition],
+    ) -> Option<Columns> {
+        let normalize = |header: &str| {
+            header
+                .trim_start_matches('\u{feff}')
+                .trim()
+                .to_lowercase()
+                .replace(['_', '-'], " ")
+        };
+        let headers: Vec<String> = headers.iter().map(normalize).collect();
+        let find = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+        Some(Columns {
+            email: find(&["email", "email address", "e mail"])?,
+            name: find(&["name", "full name"]),
+            first_name: find(&["first name"]),
+            last_name: find(&["last name"]),
+            attributes: definitions
+                .iter()
+                .filter_map(|d| {
+                    let column = headers.iter().position(|h| *h == normalize(&d.name))?;
+                    Some((d.name.clone(), column))
+                })
+                .collect(),
+        })
+    }
+
+    fn email(&self, record: &StringRecord) -> String {
+        record.get(self.email).unwrap_or_default().to_string()
+    }
+
+    fn name(&self, record: &StringRecord) -> String {
+        let field = |column: Option<usize>| column.and_then(|c| record.get(c)).unwrap_or_default();
+        match self.name {
+            Some(column) => record.get(column).unwrap_or_default().to_string(),
+            None => format!("{} {}", field(self.first_name), field(self.last_name))
+                .trim()
+                .to_string(),
+        }
+    }
+
+    fn attributes<'a>(
+        &'a self,
+        record: &'a StringRecord,
+    ) -> impl Iterator<Item = (String, String)> + 'a {
+        self.attributes.iter().map(|(name, column)| {
+            let value = record.get(*column).unwrap_or_default();
+            (name.clone(), value.to_string())
+        })
+    }
+}
+
+/// Streams the CSV, validating every row like a signup would, and upserts the valid rows into
+/// `list` in batches. New subscribers and new list memberships get `status`, existing ones are
+/// left as they are. No confirmation emails are sent.
+#[instrument(name = "Importing subscribers", skip(pool, settings, list, source), fields(list = %list.slug))]
+pub async fn import_subscribers<R: AsyncRead + Unpin + Send>(
+    pool: &PgPool,
+    settings: &SubscriptionSettings,
+    list: &MailingList,
+    status: SubscriberStatus,
+    source: R,
+) -> Result<ImportSummary, ImportError> {
+    let domain_policy = DomainPolicy::load(pool, settings.block_disposable_domains).await?;
+    let mut reader = AsyncReaderBuilder::new()
+        .flexible(true)
+        .create_reader(source);
+    let headers = reader.headers().await.map_err(ImportError::InvalidCsv)?;
+    let columns = Columns::from_headers(headers, &settings.attributes)
+        .ok_or(ImportError::MissingEmailColumn)?;
+
+    let mut summary = ImportSummary::default();
+    let mut seen_emails = HashSet::new();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut record = StringRecord::new();
+    loop {
+        match reader.read_record(&mut record).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) if e.is_io_error() => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to read the file")
+                    .into());
+            }
+            // e.g. invalid UTF-8, the reader carries on with the next row
+            Err(e) => {
+                summary.rejected.push(RejectedRow {
+                    line: e.position().map_or(0, |p| p.line()),
+                    email: String::new(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        }
+        let line = record.position().map_or(0, |p| p.line());
+        let email = columns.email(&record);
+        let subscriber = NewSubscriber::with_attributes(
+            columns.name(&record),
+            email.clone(),
+            &settings.attributes,
+            columns.attributes(&record),
+        )
+        .and_then(|subscriber| {
+            domain_policy
+                .check(&subscriber.email)
+                .map_err(|e| InvalidNewSubscriber(vec![FieldError::email(e)]))?;
+            Ok(subscriber)
+        });
+        let reason = match subscriber {
+            Ok(subscriber) if seen_emails.insert(subscriber.email.as_ref().to_lowercase()) => {
+                batch.push(subscriber);
+                if batch.len() == IMPORT_BATCH_SIZE {
+                    upsert_batch(pool, list, status, &batch, &mut summary).await?;
+                    batch.clear();
+                }
+                continue;
+            }
+            Ok(_) => "The email address appears earlier in the file.".to_string(),
+            Err(e) => e.to_string(),
+        };
+        summary.rejected.push(RejectedRow {
+            line,
+            email,
+            reason,
+        });
+    }
+    if !batch.is_empty() {
+        upsert_batch(pool, list, status, &batch, &mut summary).await?;
+    }
+
+    info!(
+        n_created = summary.n_created,
+        n_updated = summary.n_updated,
+        n_rejected = summary.rejected.len(),
+        "Imported subscribers"
+    );
+    Ok(summary)
+}
+
+/// Keeps the outcome of an import, so the rejected rows can be downloaded later
+#[instrument(name = "Saving an import", skip(pool, list, summary))]
+pub async fn save_import(
+    pool: &PgPool,
+    created_by: Option<Uuid>,
+    file_name: &str,
+    list: &MailingList,
+    status: SubscriberStatus,
+    summary: &ImportSummary,
+) -> anyhow::Result<Uuid> {
+    let import_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_imports (
+            import_id, created_by, file_name, list_id, status,
+            n_created, n_updated, n_rejected, rejected_report
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        import_id,
+        created_by,
+        file_name,
+        list.list_id,
+        status.as_str(),
+        i32::try_from(summary.n_created)?,
+        i32::try_from(summary.n_updated)?,
+        i32::try_from(summary.rejected.len())?,
+        summary.rejected_csv().await?,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to save the import")?;
+    Ok(import_id)
+}
+
+async fn upsert_batch(
+    pool: &PgPool,
+    list: &MailingList,
+    status: SubscriberStatus,
+    batch: &[NewSubscriber],
+    summary: &mut ImportSummary,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let unsubscribed_at = (status == SubscriberStatus::Unsubscribed).then_some(now);
+    let ids: Vec<Uuid> = batch.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = batch.iter().map(|s| s.email.as_ref().to_string()).collect();
+    let lowercase_emails: Vec<String> = emails.iter().map(|e| e.to_lowercase()).collect();
+    let names: Vec<String> = batch.iter().map(|s| s.name.as_ref().to_string()).collect();
+    let tokens: Vec<String> = batch.iter().map(|_| generate_token()).collect();
+    let attributes: Vec<serde_json::Value> = batch.iter().map(|s| s.attributes.to_json()).collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let n_created = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, unsubscribed_at, unsubscribe_token, attributes
+        )
+        SELECT id, email, name, $5, $6, $7, unsubscribe_token, attributes
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $8::jsonb[])
+            AS t(id, email, name, unsubscribe_token, attributes)
+        ON CONFLICT (lower(email)) DO NOTHING
+        "#,
+        &ids,
+        &emails,
+        &names,
+        &tokens,
+        now,
+        status.as_str(),
+        unsubscribed_at,
+        &attributes,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to insert subscribers")?
+    .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET attributes = s.attributes || u.attributes
+        FROM UNNEST($1::text[], $2::jsonb[]) AS u(email, attributes)
+        WHERE lower(s.email) = u.email AND u.attributes <> '{}'::jsonb
+        "#,
+        &lowercase_emails,
+        &attributes,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the attributes of existing subscribers")?;
+    // never re-subscribe someone who opted out
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at, unsubscribed_at)
+        SELECT $1, s.id, $3, $4, $5
+        FROM subscriptions s
+        WHERE lower(s.email) = ANY($2) AND (s.status <> 'unsubscribed' OR $3 = 'unsubscribed')
+        ON CONFLICT (list_id, subscriber_id) DO NOTHING
+        "#,
+        list.list_id,
+        &lowercase_emails,
+        status.as_str(),
+        now,
+        unsubscribed_at,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to insert list memberships")?;
+    // same summary as `refresh_subscriber_status`, for the whole batch at once
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = summary.status,
+            unsubscribed_at = CASE
+                WHEN summary.status = 'unsubscribed'
+                THEN COALESCE(subscriptions.unsubscribed_at, $2)
+            END
+        FROM (
+            SELECT m.subscriber_id, CASE
+                WHEN bool_or(m.status = 'confirmed') THEN 'confirmed'
+                WHEN bool_or(m.status = 'pending_confirmation') THEN 'pending_confirmation'
+                ELSE 'unsubscribed'
+            END AS status
+            FROM list_memberships m
+            JOIN subscriptions s ON s.id = m.subscriber_id
+            WHERE lower(s.email) = ANY($1)
+            GROUP BY m.subscriber_id
+        ) AS summary
+        WHERE id = summary.subscriber_id
+        "#,
+        &lowercase_emails,
+        now,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to refresh subscriber statuses")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers")?;
+
+    summary.n_created += n_created;
+    summary.n_updated += batch.len() as u64 - n_created;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Columns;
+    use crate::domain::{AttributeDefinition, AttributeKind};
+    use csv_async::StringRecord;
+
+    #[test]
+    fn mailchimp_headers_are_recognised() {
+        let headers = StringRecord::from(vec![
+            "\u{feff}Email Address",
+            "First Name",
+            "Last Name",
+            "Country",
+            "MEMBER_RATING",
+        ]);
+        let definitions = [AttributeDefinition {
+            name: "country".to_string(),
+            kind: AttributeKind::Text,
+        }];
+
+        let columns = Columns::from_headers(&headers, &definitions).unwrap();
+
+        assert_eq!(
+            columns,
+            Columns {
+                email: 0,
+                name: None,
+                first_name: Some(1),
+                last_name: Some(2),
+                attributes: vec![("country".to_string(), 3)],
+            }
+        );
+        let record = StringRecord::from(vec!["a@example.com", "Ursula", "Le Guin", "US", "2"]);
+        assert_eq!(columns.name(&record), "Ursula Le Guin");
+    }
+
+    #[test]
+    fn an_email_column_is_required() {
+        let headers = StringRecord::from(vec!["name", "phone"]);
+        assert!(Columns::from_headers(&headers, &[]).is_none());
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::domain::{
+    AttributeDefinition, MailingList, Segment, SegmentError, SubscriberFilter, SubscriberStatus,
+    TagName, TagNameError, bind_values,
+};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagAction {
+    Add,
+    Remove,
+}
+
+/// Unvalidated [`SubscriberFilter`], as sent by API clients and the admin bulk form
+#[derive(Deserialize, Debug, Default)]
+pub struct FilterParameters {
+    pub status: Option<SubscriberStatus>,
+    pub list: Option<String>,
+    pub segment: Option<String>,
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidFilter {
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error(transparent)]
+    Segment(#[from] SegmentError),
+    #[error(transparent)]
+    Tag(#[from] TagNameError),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl FilterParameters {
+    pub async fn into_filter(
+        self,
+        pool: &PgPool,
+        attributes: &[AttributeDefinition],
+    ) -> Result<SubscriberFilter, InvalidFilter> {
+        if let Some(list) = &self.list
+            && MailingList::find(pool, Some(list)).await?.is_none()
+        {
+            return Err(InvalidFilter::UnknownList(list.clone()));
+        }
+        Ok(SubscriberFilter {
+            status: self.status,
+            list: self.list,
+            segment: self
+                .segment
+                .as_deref()
+                .map(|segment| Segment::parse(segment, attributes))
+                .transpose()?,
+            include_tags: TagName::parse_all(&self.include_tags)?,
+            exclude_tags: TagName::parse_all(&self.exclude_tags)?,
+        })
+    }
+}
+
+#[instrument(name = "Retrieving subscriber tags", skip(pool))]
+pub async fn get_subscriber_tags(pool: &PgPool, subscriber_id: Uuid) -> Result<Vec<String>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT t.name
+        FROM subscriber_tags st
+        JOIN tags t ON t.tag_id = st.tag_id
+        WHERE st.subscriber_id = $1
+        ORDER BY t.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber tags")?;
+    Ok(rows.into_iter().map(|r| r.name).collect())
+}
+
+/// Returns whether the subscriber exists, tags are created as needed
+#[instrument(name = "Tagging a subscriber", skip(pool))]
+pub async fn add_tags(pool: &PgPool, subscriber_id: Uuid, tags: &[TagName]) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    if !subscriber_exists(&mut transaction, subscriber_id).await? {
+        return Ok(false);
+    }
+    let names = ensure_tags(&mut transaction, tags).await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag_id)
+        SELECT $1, tag_id FROM tags WHERE name = ANY($2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        &names,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to tag the subscriber")?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Returns whether the subscriber exists, removing a tag they don't have is not an error
+#[instrument(name = "Untagging a subscriber", skip(pool))]
+pub async fn remove_tag(pool: &PgPool, subscriber_id: Uuid, tag: &TagName) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    if !subscriber_exists(&mut transaction, subscriber_id).await? {
+        return Ok(false);
+    }
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriber_tags st
+        USING tags t
+        WHERE st.tag_id = t.tag_id AND st.subscriber_id = $1 AND t.name = $2
+        "#,
+        subscriber_id,
+        tag.as_ref(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to untag the subscriber")?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Adds or removes the tags on every subscriber matching the filter, returns the number of
+/// tag assignments that changed
+#[instrument(name = "Bulk tagging subscribers", skip(pool, filter))]
+pub async fn bulk_tag(
+    pool: &PgPool,
+    action: TagAction,
+    tags: &[TagName],
+    filter: &SubscriberFilter,
+) -> Result<u64> {
+    let mut transaction = pool.begin().await?;
+    // removing a tag nobody has must not create it
+    let names = match action {
+        TagAction::Add => ensure_tags(&mut transaction, tags).await?,
+        TagAction::Remove => tags.iter().map(ToString::to_string).collect(),
+    };
+    let (condition, params) = filter.to_sql(2);
+    // the filter is compiled to a parameterized condition, so these can't be checked at
+    // compile time
+    let sql = match action {
+        TagAction::Add => format!(
+            r#"
+            INSERT INTO subscriber_tags (subscriber_id, tag_id)
+            SELECT s.id, t.tag_id
+            FROM subscriptions s
+            JOIN tags t ON t.name = ANY($1)
+            WHERE {condition}
+            ON CONFLICT DO NOTHING
+            "#
+        ),
+        TagAction::Remove => format!(
+            r#"
+            DELETE FROM subscriber_tags st
+            USING tags t, subscriptions s
+            WHERE st.tag_id = t.tag_id AND st.subscriber_id = s.id AND t.name = ANY($1)
+                AND {condition}
+            "#
+        ),
+    };
+    let n_changed = bind_values(sqlx::query(&sql).bind(&names), params)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to bulk tag subscribers")?
+        .rows_affected();
+    transaction.commit().await?;
+    Ok(n_changed)
+}
+
+/// Removes the tag from every subscriber, returns whether it existed
+#[instrument(name = "Deleting a tag", skip(pool))]
+pub async fn delete_tag(pool: &PgPool, tag: &TagName) -> Result<bool> {
+    let n_deleted = sqlx::query!(r#"DELETE FROM tags WHERE name = $1"#, tag.as_ref())
+        .execute(pool)
+        .await
+        .context("Failed to delete the tag")?
+        .rows_affected();
+    Ok(n_deleted > 0)
+}
+
+async fn subscriber_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1 FOR SHARE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to retrieve the subscriber")?;
+    Ok(row.is_some())
+}
+
+/// Creates the tags that don't exist yet, returns their names
+async fn ensure_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    tags: &[TagName],
+) -> Result<Vec<String>> {
+    let names: Vec<String> = tags.iter().map(ToString::to_string).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO tags (tag_id, name)
+        SELECT gen_random_uuid(), name FROM UNNEST($1::text[]) AS name
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        &names,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to create tags")?;
+    Ok(names)
+}
@@ -0,0 +1,44 @@
+use actix_web::{
+    HttpResponse,
+    http::header::{ContentType, LOCATION},
+};
+
+/// 303 redirect, the browser follows it with a GET
+pub fn see_other(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+/// Wraps `body` in the boilerplate shared by all HTML pages, `body` must already be escaped
+pub fn html_page(title: &str, body: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>"#,
+        ))
+}
+
+/// Renders flash messages as paragraphs
+pub fn flash_messages_html(
+    flash_messages: &actix_web_flash_messages::IncomingFlashMessages,
+) -> String {
+    flash_messages
+        .iter()
+        .map(|m| {
+            format!(
+                "<p><i>{}</i></p>\n",
+                htmlescape::encode_minimal(m.content())
+            )
+        })
+        .collect()
+}
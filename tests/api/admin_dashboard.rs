@@ -0,0 +1,62 @@
+use anyhow::Result;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_access_the_admin_dashboard() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = app.get_admin_dashboard().await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_tampered_session_cookie_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?
+        .get(format!("{}/admin/dashboard", app.config.app_address()))
+        .header("Cookie", "id=not-a-signed-session-key")
+        .send()
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn logout_clears_session_state() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act - Part 1 - Login
+    app.login().await?;
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_admin_dashboard_html().await?;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+
+    // Act - Part 3 - Logout
+    let response = app.post_logout().await?;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - Follow the redirect
+    let html_page = app.get_login_html().await?;
+    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    // Act - Part 5 - Attempt to load the admin dashboard
+    let response = app.get_admin_dashboard().await?;
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
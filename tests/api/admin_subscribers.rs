@@ -0,0 +1,270 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+/// Inserts a subscriber straight into the database, bypassing the signup flow
+async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    status: &str,
+    age: Duration,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, 'name', $3, $4, $5)
+        "#,
+        id,
+        email,
+        Utc::now() - age,
+        status,
+        Uuid::new_v4().to_string(),
+    )
+    .execute(&app.pool)
+    .await?;
+    Ok(id)
+}
+
+async fn get_status(app: &TestApp, id: Uuid) -> Result<Option<String>> {
+    Ok(
+        sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", id)
+            .fetch_optional(&app.pool)
+            .await?
+            .map(|r| r.status),
+    )
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let id = insert_subscriber(
+        &app,
+        "a@example.com",
+        "pending_confirmation",
+        Duration::zero(),
+    )
+    .await?;
+
+    // Act
+    let list = app.get_page("/admin/subscribers").await?;
+    let delete = app
+        .post_admin_action(&format!("/admin/subscribers/{id}/delete"))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&list, "/login");
+    assert_is_redirect_to(&delete, "/login");
+    assert!(get_status(&app, id).await?.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn the_dashboard_shows_counts_by_status() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    insert_subscriber(&app, "a@example.com", "confirmed", Duration::zero()).await?;
+    insert_subscriber(&app, "b@example.com", "confirmed", Duration::zero()).await?;
+    insert_subscriber(&app, "c@example.com", "unsubscribed", Duration::zero()).await?;
+    app.login().await?;
+
+    // Act
+    let html_page = app.get_admin_dashboard_html().await?;
+
+    // Assert
+    assert!(html_page.contains(">confirmed</a></td><td>2</td>"));
+    assert!(html_page.contains(">pending_confirmation</a></td><td>0</td>"));
+    assert!(html_page.contains(">unsubscribed</a></td><td>1</td>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    insert_subscriber(&app, "confirmed@example.com", "confirmed", Duration::zero()).await?;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        Duration::zero(),
+    )
+    .await?;
+    app.login().await?;
+
+    // Act
+    let all = app.get_page("/admin/subscribers").await?.text().await?;
+    let confirmed = app
+        .get_page("/admin/subscribers?status=confirmed")
+        .await?
+        .text()
+        .await?;
+    let invalid = app.get_page("/admin/subscribers?status=bogus").await?;
+
+    // Assert
+    assert!(all.contains("confirmed@example.com") && all.contains("pending@example.com"));
+    assert!(confirmed.contains("confirmed@example.com"));
+    assert!(!confirmed.contains("pending@example.com"));
+    assert_eq!(invalid.status().as_u16(), 400);
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated_newest_first() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    for i in 0..30 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{i:02}@example.com"),
+            "confirmed",
+            Duration::minutes(i),
+        )
+        .await?;
+    }
+    app.login().await?;
+
+    // Act
+    let first = app.get_page("/admin/subscribers").await?.text().await?;
+    let second = app
+        .get_page("/admin/subscribers?page=2")
+        .await?
+        .text()
+        .await?;
+
+    // Assert
+    assert!(first.contains("subscriber00@example.com"));
+    assert!(first.contains("subscriber24@example.com"));
+    assert!(!first.contains("subscriber25@example.com"));
+    assert!(first.contains("Page 1 of 2"));
+    assert!(second.contains("subscriber29@example.com"));
+    assert!(!second.contains("subscriber00@example.com"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_single_subscriber_can_be_viewed() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let id = insert_subscriber(
+        &app,
+        "a@example.com",
+        "pending_confirmation",
+        Duration::zero(),
+    )
+    .await?;
+    app.login().await?;
+
+    // Act
+    let found = app.get_page(&format!("/admin/subscribers/{id}")).await?;
+    let missing = app
+        .get_page(&format!("/admin/subscribers/{}", Uuid::new_v4()))
+        .await?;
+
+    // Assert
+    assert_eq!(found.status().as_u16(), 200);
+    assert!(found.text().await?.contains("a@example.com"));
+    assert_eq!(missing.status().as_u16(), 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pending_subscribers_can_be_confirmed_manually() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let pending = insert_subscriber(
+        &app,
+        "a@example.com",
+        "pending_confirmation",
+        Duration::zero(),
+    )
+    .await?;
+    let unsubscribed =
+        insert_subscriber(&app, "b@example.com", "unsubscribed", Duration::zero()).await?;
+    app.login().await?;
+
+    // Act
+    let response = app
+        .post_admin_action(&format!("/admin/subscribers/{pending}/confirm"))
+        .await?;
+    app.post_admin_action(&format!("/admin/subscribers/{unsubscribed}/confirm"))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{pending}"));
+    assert_eq!(
+        get_status(&app, pending).await?.as_deref(),
+        Some("confirmed")
+    );
+    // someone who opted out is never re-subscribed
+    assert_eq!(
+        get_status(&app, unsubscribed).await?.as_deref(),
+        Some("unsubscribed")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_can_be_unsubscribed_manually() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let id = insert_subscriber(&app, "a@example.com", "confirmed", Duration::zero()).await?;
+    app.login().await?;
+
+    // Act
+    let response = app
+        .post_admin_action(&format!("/admin/subscribers/{id}/unsubscribe"))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{id}"));
+    let html_page = app
+        .get_page(&format!("/admin/subscribers/{id}"))
+        .await?
+        .text()
+        .await?;
+    assert!(html_page.contains("The subscriber has been unsubscribed."));
+    assert_eq!(get_status(&app, id).await?.as_deref(), Some("unsubscribed"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_can_be_deleted() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let id = insert_subscriber(
+        &app,
+        "a@example.com",
+        "pending_confirmation",
+        Duration::zero(),
+    )
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT 'token', $1, list_id FROM lists WHERE slug = 'newsletter'
+        "#,
+        id,
+    )
+    .execute(&app.pool)
+    .await?;
+    app.login().await?;
+
+    // Act
+    let response = app
+        .post_admin_action(&format!("/admin/subscribers/{id}/delete"))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(get_status(&app, id).await?.is_none());
+    let n_tokens = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_tokens, 0);
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use anyhow::Result;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use zero2prod::authentication::Role;
+
+use crate::helpers::{
+    TestApp, TestUser, assert_is_redirect_to, create_confirmed_subscriber, spawn_app,
+};
+
+async fn post_newsletters_with_api_key(app: &TestApp, api_key: &str) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .bearer_auth(api_key)
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .send()
+        .await?)
+}
+
+#[tokio::test]
+async fn keys_are_stored_hashed_and_listed_by_prefix() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act
+    let key = app.create_api_key(&["stats:read"]).await?;
+
+    // Assert
+    let row = sqlx::query!("SELECT prefix, key_hash, scopes FROM api_keys")
+        .fetch_one(&app.pool)
+        .await?;
+    assert!(key.starts_with(&format!("{}_", row.prefix)));
+    assert_ne!(row.key_hash, key);
+    assert_eq!(row.scopes, vec!["stats:read"]);
+    let html = app.get_page("/admin/api-keys").await?.text().await?;
+    assert!(html.contains(&row.prefix));
+    assert!(!html.contains(&key));
+    Ok(())
+}
+
+#[tokio::test]
+async fn stats_can_be_read_with_a_scoped_key() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+    app.login().await?;
+    let key = app.create_api_key(&["stats:read"]).await?;
+
+    // Act
+    let response = app.get_api("/api/v1/stats", &key).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["subscribers"]["confirmed"], 1);
+    assert_eq!(body["subscribers"]["pending_confirmation"], 0);
+    let last_used_at = sqlx::query!("SELECT last_used_at FROM api_keys")
+        .fetch_one(&app.pool)
+        .await?
+        .last_used_at;
+    assert!(last_used_at.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_key_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let missing = reqwest::get(format!("{}/api/v1/stats", app.config.app_address())).await?;
+    let unknown = app.get_api("/api/v1/stats", "z2p_unknown_key").await?;
+
+    // Assert
+    assert_eq!(missing.status().as_u16(), 401);
+    assert_eq!(missing.headers()["WWW-Authenticate"], "Bearer");
+    assert_eq!(unknown.status().as_u16(), 401);
+    let problem: serde_json::Value = unknown.json().await?;
+    assert_eq!(problem["code"], "invalid_api_key");
+    Ok(())
+}
+
+#[tokio::test]
+async fn keys_without_the_scope_are_forbidden() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let key = app.create_api_key(&["subscribers:write"]).await?;
+
+    // Act
+    let stats = app.get_api("/api/v1/stats", &key).await?;
+    let publish = post_newsletters_with_api_key(&app, &key).await?;
+
+    // Assert
+    assert_eq!(stats.status().as_u16(), 403);
+    assert_eq!(publish.status().as_u16(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletters_can_be_published_with_a_key() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+    app.login().await?;
+    let key = app.create_api_key(&["newsletters:publish"]).await?;
+
+    // Act
+    let response = post_newsletters_with_api_key(&app, &key).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    Ok(())
+}
+
+#[tokio::test]
+async fn keys_stop_publishing_once_their_creator_is_demoted() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let key = app.create_api_key(&["newsletters:publish"]).await?;
+    sqlx::query!(
+        "UPDATE users SET role = 'viewer' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.pool)
+    .await?;
+
+    // Act
+    let response = post_newsletters_with_api_key(&app, &key).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "insufficient_role");
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoked_and_expired_keys_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let revoked = app.create_api_key(&["stats:read"]).await?;
+    let expired = app.create_api_key(&["stats:read"]).await?;
+    let revoked_id = sqlx::query!(
+        "SELECT api_key_id FROM api_keys WHERE $1 LIKE prefix || '%'",
+        revoked
+    )
+    .fetch_one(&app.pool)
+    .await?
+    .api_key_id;
+    sqlx::query!(
+        "UPDATE api_keys SET expires_at = now() - interval '1 minute' WHERE $1 LIKE prefix || '%'",
+        expired
+    )
+    .execute(&app.pool)
+    .await?;
+
+    // Act
+    let response = app
+        .post_admin_action(&format!("/admin/api-keys/{revoked_id}/revoke"))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/api-keys");
+
+    // Assert
+    for key in [revoked, expired] {
+        let response = app.get_api("/api/v1/stats", &key).await?;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_keys() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let publisher = TestUser::generate_with_role(Role::Publisher);
+    publisher.store(&app.pool).await?;
+    app.login_as(&publisher).await?;
+
+    // Act
+    let list = app.get_page("/admin/api-keys").await?;
+    let create = app
+        .post_admin_form("/admin/api-keys", &[("name", "key"), ("stats:read", "on")])
+        .await?;
+
+    // Assert
+    assert_eq!(list.status().as_u16(), 403);
+    assert_eq!(create.status().as_u16(), 403);
+    Ok(())
+}
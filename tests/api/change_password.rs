@@ -0,0 +1,141 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+const NEW_PASSWORD: &str = "a-new-password-with-digits-1234";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_change_password_form() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = app.get_page("/admin/password").await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_your_password() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_password_fields_must_match() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act - Part 1 - Try to change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": format!("{NEW_PASSWORD}-typo"),
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_page("/admin/password").await?.text().await?;
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_password_must_be_strong_enough() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act - Part 1 - Try to change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "short1",
+            "new_password_check": "short1",
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_page("/admin/password").await?.text().await?;
+    assert!(html_page.contains("must be at least 12 characters long"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn current_password_must_be_valid() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act - Part 1 - Try to change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_page("/admin/password").await?.text().await?;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn changing_password_works() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act - Part 1 - Change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_page("/admin/password").await?.text().await?;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+
+    // Act - Part 3 - Logout
+    let response = app.post_logout().await?;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - Login using the new password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD,
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    Ok(())
+}
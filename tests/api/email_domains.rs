@@ -0,0 +1,93 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero2prod::authentication::Role;
+
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, post_subscriptions, spawn_app};
+
+async fn add_rule(app: &TestApp, domain: &str, rule: &str) -> Result<reqwest::Response> {
+    app.post_admin_form(
+        "/admin/email-domains",
+        &serde_json::json!({ "domain": domain, "rule": rule }),
+    )
+    .await
+}
+
+async fn email_error_code(response: reqwest::Response) -> Result<String> {
+    let problem: serde_json::Value = response.json().await?;
+    Ok(problem["errors"][0]["code"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn signups_from_disposable_domains_are_rejected_with_a_reason() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40mailinator.com".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(email_error_code(response).await?, "email_domain_disposable");
+    Ok(())
+}
+
+#[tokio::test]
+async fn admins_can_block_and_allow_domains() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = add_rule(&app, "Example.com", "block").await?;
+    assert_is_redirect_to(&response, "/admin/email-domains");
+    add_rule(&app, "mailinator.com", "allow").await?;
+    let blocked = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40news.example.com".to_string(),
+    )
+    .await?;
+    let allowed = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40mailinator.com".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(blocked.status().as_u16(), 400);
+    assert_eq!(email_error_code(blocked).await?, "email_domain_blocked");
+    assert_eq!(allowed.status().as_u16(), 200);
+    let html = app.get_page("/admin/email-domains").await?.text().await?;
+    assert!(html.contains("example.com"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn viewers_cannot_change_domain_rules() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let viewer = TestUser::generate_with_role(Role::Viewer);
+    viewer.store(&app.pool).await?;
+    app.login_as(&viewer).await?;
+
+    // Act
+    let list = app.get_page("/admin/email-domains").await?;
+    let add = add_rule(&app, "example.com", "block").await?;
+
+    // Assert
+    assert_eq!(list.status().as_u16(), 200);
+    assert_eq!(add.status().as_u16(), 403);
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use uuid::Uuid;
+use zero2prod::{authentication::Role, subscriber_export::EXPORT_BATCH_SIZE};
+
+use crate::helpers::{TestApp, TestUser, spawn_app};
+
+/// Inserts a subscriber confirmed on the default list, tagged through the admin pages
+async fn insert_tagged_subscriber(app: &TestApp, tags: &str) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, unsubscribe_token, attributes
+        )
+        VALUES ($1, 'ursula@example.com', 'Ursula, Le Guin', now(), 'confirmed', $2, $3)
+        "#,
+        id,
+        Uuid::new_v4().to_string(),
+        serde_json::json!({ "country": "US" }),
+    )
+    .execute(&app.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT list_id, $1, 'confirmed', now() FROM lists WHERE slug = 'newsletter'
+        "#,
+        id,
+    )
+    .execute(&app.pool)
+    .await?;
+    app.post_admin_form(&format!("/admin/subscribers/{id}/tags"), &[("tags", tags)])
+        .await?;
+    Ok(id)
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let id = insert_tagged_subscriber(&app, "beta, vip").await?;
+
+    // Act
+    let response = app.get_page("/admin/subscribers/export?format=csv").await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    assert!(
+        response.headers()["Content-Disposition"]
+            .to_str()?
+            .starts_with(r#"attachment; filename="subscribers-"#)
+    );
+    let csv = response.text().await?;
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,email,name,status,subscribed_at,unsubscribed_at,lists,tags,attributes"
+    );
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with(&format!(
+        r#"{id},ursula@example.com,"Ursula, Le Guin",confirmed,"#
+    )));
+    assert!(lines[1].ends_with(r#",newsletter:confirmed,beta;vip,"{""country"":""US""}""#));
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_json_lines() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let id = insert_tagged_subscriber(&app, "beta").await?;
+
+    // Act
+    let response = app
+        .get_page("/admin/subscribers/export?format=ndjson")
+        .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/x-ndjson");
+    let body = response.text().await?;
+    let subscribers: Vec<serde_json::Value> = body
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(subscribers.len(), 1);
+    let subscriber = &subscribers[0];
+    assert_eq!(subscriber["id"], id.to_string());
+    assert_eq!(subscriber["status"], "confirmed");
+    assert!(subscriber["subscribed_at"].is_string());
+    assert!(subscriber["unsubscribed_at"].is_null());
+    assert_eq!(subscriber["lists"][0]["list"], "newsletter");
+    assert_eq!(subscriber["lists"][0]["status"], "confirmed");
+    assert_eq!(subscriber["tags"], serde_json::json!(["beta"]));
+    assert_eq!(
+        subscriber["attributes"],
+        serde_json::json!({ "country": "US" })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn csv_cells_that_look_like_formulas_are_escaped() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let name = r#"=HYPERLINK("https://example.com","Click")"#;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, '@ursula@example.com', $2, now(), 'confirmed', 'token')
+        "#,
+        Uuid::new_v4(),
+        name,
+    )
+    .execute(&app.pool)
+    .await?;
+
+    // Act
+    let csv = app
+        .get_page("/admin/subscribers/export?format=csv")
+        .await?
+        .text()
+        .await?;
+    let ndjson = app
+        .get_page("/admin/subscribers/export?format=ndjson")
+        .await?
+        .text()
+        .await?;
+
+    // Assert
+    let row = csv.lines().nth(1).unwrap();
+    assert!(row.contains(
+        r#",'@ursula@example.com,"'=HYPERLINK(""https://example.com"",""Click"")",confirmed,"#
+    ));
+    let subscriber: serde_json::Value = serde_json::from_str(ndjson.trim())?;
+    assert_eq!(subscriber["name"], name);
+    assert_eq!(subscriber["email"], "@ursula@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn exports_larger_than_a_batch_are_complete() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let n_subscribers = EXPORT_BATCH_SIZE as i32 * 2 + 1;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        SELECT gen_random_uuid(), 'user' || n || '@example.com', 'name', now(), 'confirmed', n::text
+        FROM generate_series(1, $1) AS n
+        "#,
+        n_subscribers,
+    )
+    .execute(&app.pool)
+    .await?;
+
+    // Act
+    let body = app
+        .get_page("/admin/subscribers/export?format=ndjson")
+        .await?
+        .text()
+        .await?;
+
+    // Assert
+    assert_eq!(body.lines().count(), n_subscribers as usize);
+    Ok(())
+}
+
+#[tokio::test]
+async fn viewers_cannot_export_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let user = TestUser::generate_with_role(Role::Viewer);
+    user.store(&app.pool).await?;
+    app.login_as(&user).await?;
+
+    // Act
+    let response = app.get_page("/admin/subscribers/export").await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    Ok(())
+}
@@ -1,6 +1,176 @@
+use std::ops::Deref;
+
 use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version, password_hash::SaltString};
+use reqwest::Url;
+use sqlx::PgPool;
+use tempfile::TempDir;
+use uuid::Uuid;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use zero2prod::{
+    AppHandle,
+    authentication::Role,
+    configuration::{EmailTransportKind, Settings},
+    email_client::{OutboxMessage, latest_message_for},
+    issue_delivery_worker::{ExecutionOutcome, try_execute_task},
+    spawn_test_app_with,
+};
+
+pub(crate) struct TestApp {
+    pub handle: AppHandle,
+    /// `None` for apps spawned with [`spawn_app_with_outbox`], see [`TestApp::email_server`]
+    email_server: Option<MockServer>,
+    /// Deleted when the app is dropped, only set for apps spawned with [`spawn_app_with_outbox`]
+    _outbox_dir: Option<TempDir>,
+    pub test_user: TestUser,
+    /// Keeps cookies between requests and doesn't follow redirects, so tests can inspect them
+    pub api_client: reqwest::Client,
+}
+
+pub(crate) struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+    pub email: String,
+    pub role: Role,
+}
+
+impl TestUser {
+    /// An owner, allowed to do everything
+    pub fn generate() -> Self {
+        Self::generate_with_role(Role::Owner)
+    }
+
+    pub fn generate_with_role(role: Role) -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+            role,
+        }
+    }
+
+    pub async fn store(&self, pool: &PgPool) -> Result<()> {
+        let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes()).unwrap();
+        // cheap parameters to keep tests fast, verification reads them from the PHC string
+        let password_hash = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(15000, 2, 1, None).unwrap(),
+        )
+        .hash_password(self.password.as_bytes(), &salt)
+        .unwrap()
+        .to_string();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (user_id, username, password_hash, email, role)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            self.user_id,
+            self.username,
+            password_hash,
+            self.email,
+            self.role.as_str(),
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl TestApp {
+    /// Mock email API the app sends emails to
+    pub fn email_server(&self) -> &MockServer {
+        self.email_server
+            .as_ref()
+            .expect("The app writes emails to an outbox, not to the email API")
+    }
+}
+
+impl Deref for TestApp {
+    type Target = AppHandle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+/// Confirmation links embedded in the request to the email API
+pub(crate) struct ConfirmationLinks {
+    pub html: Url,
+    pub plain_text: Url,
+}
+
+/// Spawns the app with the email client pointed at a mock server
+pub(crate) async fn spawn_app() -> Result<TestApp> {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like [`spawn_app`], with further changes to the configuration
+pub(crate) async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> Result<TestApp> {
+    let email_server = MockServer::start().await;
+    let handle = spawn_test_app_with(|config| {
+        config.email_client.kind = EmailTransportKind::Http;
+        config.email_client.base_url = email_server.uri();
+        // keep retries against the mock server fast
+        config.email_client.retry.base_delay_milliseconds = 1;
+        config.email_client.retry.jitter = false;
+        configure(config);
+    })
+    .await?;
+
+    let test_user = TestUser::generate();
+    test_user.store(&handle.pool).await?;
+
+    Ok(TestApp {
+        handle,
+        email_server: Some(email_server),
+        _outbox_dir: None,
+        test_user,
+        api_client: api_client()?,
+    })
+}
 
-use zero2prod::AppHandle;
+/// Spawns the app with the email client writing to a throwaway outbox directory, read emails back
+/// with [`latest_email_for`]
+pub(crate) async fn spawn_app_with_outbox() -> Result<TestApp> {
+    let outbox_dir = TempDir::with_prefix("zero2prod-outbox-")?;
+    let handle = spawn_test_app_with(|config| {
+        config.email_client.kind = EmailTransportKind::File;
+        config.email_client.outbox_dir = outbox_dir.path().to_path_buf();
+    })
+    .await?;
+
+    let test_user = TestUser::generate();
+    test_user.store(&handle.pool).await?;
+
+    Ok(TestApp {
+        handle,
+        email_server: None,
+        _outbox_dir: Some(outbox_dir),
+        test_user,
+        api_client: api_client()?,
+    })
+}
+
+fn api_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()?)
+}
+
+/// Latest email written to the outbox of an app spawned with [`spawn_app_with_outbox`]
+pub(crate) fn latest_email_for(app: &AppHandle, recipient: &str) -> Result<OutboxMessage> {
+    latest_message_for(&app.config.email_client.outbox_dir, recipient)?
+        .ok_or_else(|| anyhow::anyhow!("No email was sent to {}", recipient))
+}
 
 pub(crate) async fn post_subscriptions(app: &AppHandle, body: String) -> Result<reqwest::Response> {
     Ok(reqwest::Client::new()
@@ -10,3 +180,269 @@ pub(crate) async fn post_subscriptions(app: &AppHandle, body: String) -> Result<
         .send()
         .await?)
 }
+
+pub(crate) async fn post_newsletters(
+    app: &TestApp,
+    body: serde_json::Value,
+) -> Result<reqwest::Response> {
+    post_newsletters_as(app, &app.test_user, body).await
+}
+
+pub(crate) async fn post_newsletters_as(
+    app: &TestApp,
+    user: &TestUser,
+    body: serde_json::Value,
+) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .basic_auth(&user.username, Some(&user.password))
+        .json(&body)
+        .send()
+        .await?)
+}
+
+pub(crate) async fn post_newsletters_with_idempotency_key(
+    app: &TestApp,
+    body: serde_json::Value,
+    idempotency_key: &str,
+) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .header("Idempotency-Key", idempotency_key)
+        .json(&body)
+        .send()
+        .await?)
+}
+
+impl TestApp {
+    pub async fn post_login<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/login", self.config.app_address()))
+            .form(body)
+            .send()
+            .await?)
+    }
+
+    /// Logs in as the test user, the session cookie is kept by `api_client`
+    pub async fn login(&self) -> Result<()> {
+        self.login_as(&self.test_user).await
+    }
+
+    pub async fn login_as(&self, user: &TestUser) -> Result<()> {
+        let response = self
+            .post_login(&serde_json::json!({
+                "username": &user.username,
+                "password": &user.password,
+            }))
+            .await?;
+        assert_is_redirect_to(&response, "/admin/dashboard");
+        Ok(())
+    }
+
+    pub async fn post_two_factor(&self, code: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/login/two-factor", self.config.app_address()))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await?)
+    }
+
+    pub async fn get_login_html(&self) -> Result<String> {
+        Ok(self
+            .api_client
+            .get(format!("{}/login", self.config.app_address()))
+            .send()
+            .await?
+            .text()
+            .await?)
+    }
+
+    pub async fn get_admin_dashboard(&self) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .get(format!("{}/admin/dashboard", self.config.app_address()))
+            .send()
+            .await?)
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> Result<String> {
+        Ok(self.get_admin_dashboard().await?.text().await?)
+    }
+
+    pub async fn get_page(&self, path: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .get(format!("{}{}", self.config.app_address(), path))
+            .send()
+            .await?)
+    }
+
+    pub async fn post_admin_form<Body: serde::Serialize>(
+        &self,
+        path: &str,
+        body: &Body,
+    ) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}{}", self.config.app_address(), path))
+            .form(body)
+            .send()
+            .await?)
+    }
+
+    pub async fn post_admin_action(&self, path: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}{}", self.config.app_address(), path))
+            .send()
+            .await?)
+    }
+
+    pub async fn post_change_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/admin/password", self.config.app_address()))
+            .form(body)
+            .send()
+            .await?)
+    }
+
+    pub async fn post_forgot_password(&self, email: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/password/forgot", self.config.app_address()))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await?)
+    }
+
+    pub async fn post_reset_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/password/reset", self.config.app_address()))
+            .form(body)
+            .send()
+            .await?)
+    }
+
+    pub async fn post_logout(&self) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .post(format!("{}/admin/logout", self.config.app_address()))
+            .send()
+            .await?)
+    }
+
+    /// Creates an API key through the admin pages, the user must be logged in as an owner
+    pub async fn create_api_key(&self, scopes: &[&str]) -> Result<String> {
+        let mut form = vec![("name", "test key")];
+        form.extend(scopes.iter().map(|s| (*s, "on")));
+        let html = self
+            .post_admin_form("/admin/api-keys", &form)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let key = html
+            .split_once(r#"<code id="api-key">"#)
+            .and_then(|(_, rest)| rest.split_once("</code>"))
+            .map(|(key, _)| key.to_string())
+            .expect("The created key is shown on the page");
+        Ok(key)
+    }
+
+    pub async fn get_api(&self, path: &str, api_key: &str) -> Result<reqwest::Response> {
+        Ok(self
+            .api_client
+            .get(format!("{}{}", self.config.app_address(), path))
+            .bearer_auth(api_key)
+            .send()
+            .await?)
+    }
+}
+
+pub(crate) fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["Location"], location);
+}
+
+/// Drains the delivery queue, test apps don't run the background worker
+pub(crate) async fn dispatch_all_pending_emails(app: &AppHandle) -> Result<()> {
+    while try_execute_task(&app.pool, &app.email_client, &app.config.app.base_url).await?
+        != ExecutionOutcome::EmptyQueue
+    {}
+    Ok(())
+}
+
+/// Subscribes a new user through the public API, leaving them pending confirmation
+pub(crate) async fn create_unconfirmed_subscriber(app: &TestApp) -> Result<ConfirmationLinks> {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(app.email_server())
+        .await;
+
+    post_subscriptions(app, body.to_string())
+        .await?
+        .error_for_status()?;
+
+    let email_request = &app
+        .email_server()
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    get_confirmation_links(app, email_request)
+}
+
+pub(crate) async fn create_confirmed_subscriber(app: &TestApp) -> Result<()> {
+    let confirmation_links = create_unconfirmed_subscriber(app).await?;
+    reqwest::get(confirmation_links.html)
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Extracts the confirmation links from a request intercepted by the mock email server
+pub(crate) fn get_confirmation_links(
+    app: &AppHandle,
+    email_request: &wiremock::Request,
+) -> Result<ConfirmationLinks> {
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body)?;
+
+    let html = get_link(app, body["html"].as_str().unwrap())?;
+    let plain_text = get_link(app, body["text"].as_str().unwrap())?;
+    Ok(ConfirmationLinks { html, plain_text })
+}
+
+/// Extracts the only link in an email body, pointing it at the test app
+pub(crate) fn get_link(app: &AppHandle, s: &str) -> Result<Url> {
+    let links: Vec<_> = linkify::LinkFinder::new()
+        .links(s)
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        .collect();
+    assert_eq!(links.len(), 1);
+    let mut link = Url::parse(links[0].as_str())?;
+    // make sure we don't call random APIs on the web
+    assert_eq!(link.host_str(), Some("127.0.0.1"));
+    link.set_port(Some(app.config.app.port))
+        .expect("Failed to set port on link");
+    Ok(link)
+}
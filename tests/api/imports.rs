@@ -0,0 +1,168 @@
+use anyhow::Result;
+use reqwest::multipart::{Form, Part};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+async fn post_import(app: &TestApp, status: &str, csv: &str) -> Result<reqwest::Response> {
+    let form = Form::new()
+        .text("list", "newsletter")
+        .text("status", status.to_string())
+        .part(
+            "file",
+            Part::text(csv.to_string())
+                .file_name("subscribers.csv")
+                .mime_str("text/csv")?,
+        );
+    Ok(app
+        .api_client
+        .post(format!("{}/admin/imports", app.config.app_address()))
+        .multipart(form)
+        .send()
+        .await?)
+}
+
+#[tokio::test]
+async fn valid_rows_are_imported_and_rejected_rows_reported() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let csv = "\
+Email Address,First Name,Last Name,Member Rating
+ursula@example.com,Ursula,Le Guin,5
+not-an-email,Octavia,Butler,4
+nameless@example.com,,,3
+Ursula@example.com,Ursula,Again,2
+";
+
+    // Act - Part 1 - Upload
+    let response = post_import(&app, "confirmed", csv).await?;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, "/admin/imports");
+    let html = app.get_page("/admin/imports").await?.text().await?;
+    assert!(html.contains("1 subscribers have been created, 0 updated and 3 rows rejected."));
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.email, s.name, s.status, m.status AS membership_status
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        "#
+    )
+    .fetch_all(&app.pool)
+    .await?;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "ursula@example.com");
+    assert_eq!(saved[0].name, "Ursula Le Guin");
+    assert_eq!(saved[0].status, "confirmed");
+    assert_eq!(saved[0].membership_status, "confirmed");
+
+    // Act - Part 2 - Download the rejected rows
+    let import = sqlx::query!("SELECT import_id FROM subscriber_imports")
+        .fetch_one(&app.pool)
+        .await?;
+    let response = app
+        .get_page(&format!("/admin/imports/{}/rejected.csv", import.import_id))
+        .await?;
+
+    // Assert - Part 2
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let report = response.text().await?;
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "line,email,reason");
+    assert!(lines[1].starts_with("3,not-an-email,"));
+    assert!(lines[2].starts_with("4,nameless@example.com,"));
+    assert_eq!(
+        lines[3],
+        "5,Ursula@example.com,The email address appears earlier in the file."
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_rows_that_look_like_formulas_are_escaped() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let csv = "\
+email,name
+\"=HYPERLINK(\"\"https://example.com\"\",\"\"Click\"\")\",Mallory
+";
+    post_import(&app, "confirmed", csv).await?;
+    let import = sqlx::query!("SELECT import_id FROM subscriber_imports")
+        .fetch_one(&app.pool)
+        .await?;
+
+    // Act
+    let report = app
+        .get_page(&format!("/admin/imports/{}/rejected.csv", import.import_id))
+        .await?
+        .text()
+        .await?;
+
+    // Assert
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(
+        lines[1].starts_with(r#"2,"'=HYPERLINK(""https://example.com"",""Click"")","#),
+        "Unexpected row: {}",
+        lines[1]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn existing_subscribers_keep_their_status() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    create_confirmed_subscriber(&app).await?;
+    let existing = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    app.post_admin_action(&format!("/admin/subscribers/{}/unsubscribe", existing.id))
+        .await?;
+
+    // Act
+    let response = post_import(
+        &app,
+        "confirmed",
+        "email,name\nursula_le_guin@gmail.com,le guin\n",
+    )
+    .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/imports");
+    let html = app.get_page("/admin/imports").await?.text().await?;
+    assert!(html.contains("0 subscribers have been created, 1 updated and 0 rows rejected."));
+    // someone who opted out is never re-subscribed
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "unsubscribed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn files_without_an_email_column_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act
+    let response = post_import(&app, "confirmed", "name,phone\nle guin,555\n").await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/imports");
+    let html = app.get_page("/admin/imports").await?.text().await?;
+    assert!(html.contains("The file has no email column."));
+    let n_imports = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriber_imports"#)
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_imports, 0);
+    Ok(())
+}
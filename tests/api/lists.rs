@@ -0,0 +1,245 @@
+use anyhow::Result;
+use reqwest::Url;
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, dispatch_all_pending_emails, get_confirmation_links, get_link,
+    post_newsletters, post_subscriptions, spawn_app,
+};
+
+async fn create_list(app: &TestApp, slug: &str, name: &str) -> Result<()> {
+    sqlx::query!(
+        "INSERT INTO lists (list_id, slug, name) VALUES ($1, $2, $3)",
+        Uuid::new_v4(),
+        slug,
+        name,
+    )
+    .execute(&app.pool)
+    .await?;
+    Ok(())
+}
+
+/// Signs `email` up to `list` and returns the emailed confirmation link
+async fn subscribe_to(app: &TestApp, email: &str, list: &str) -> Result<Url> {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(app.email_server())
+        .await;
+    post_subscriptions(app, format!("name=le%20guin&email={email}&list={list}"))
+        .await?
+        .error_for_status()?;
+
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    Ok(get_confirmation_links(app, &email_request.unwrap())?.html)
+}
+
+async fn membership_status(app: &TestApp, email: &str, list: &str) -> Result<Option<String>> {
+    Ok(sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE s.email = $1 AND l.slug = $2
+        "#,
+        email,
+        list,
+    )
+    .fetch_optional(&app.pool)
+    .await?
+    .map(|r| r.status))
+}
+
+fn newsletter_body(list: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        },
+        "list": list,
+    })
+}
+
+#[tokio::test]
+async fn admins_can_add_lists() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act
+    let response = app
+        .post_admin_form(
+            "/admin/lists",
+            &serde_json::json!({ "slug": "weekly-digest", "name": "Weekly digest" }),
+        )
+        .await?;
+    let duplicate = app
+        .post_admin_form(
+            "/admin/lists",
+            &serde_json::json!({ "slug": "weekly-digest", "name": "Another digest" }),
+        )
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/lists");
+    assert_is_redirect_to(&duplicate, "/admin/lists");
+    let html = app.get_page("/admin/lists").await?.text().await?;
+    assert!(html.contains("There is already a list called weekly-digest."));
+    assert!(html.contains("Weekly digest"));
+    assert!(!html.contains("Another digest"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40example.com&list=nope".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "unknown_list");
+    Ok(())
+}
+
+#[tokio::test]
+async fn each_list_is_confirmed_separately() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_list(&app, "weekly-digest", "Weekly digest").await?;
+    let email = "ursula@example.com";
+
+    // Act
+    let newsletter_link = subscribe_to(&app, email, "newsletter").await?;
+    subscribe_to(&app, email, "weekly-digest").await?;
+    reqwest::get(newsletter_link).await?.error_for_status()?;
+
+    // Assert
+    assert_eq!(
+        membership_status(&app, email, "newsletter")
+            .await?
+            .as_deref(),
+        Some("confirmed")
+    );
+    assert_eq!(
+        membership_status(&app, email, "weekly-digest")
+            .await?
+            .as_deref(),
+        Some("pending_confirmation")
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn issues_only_reach_confirmed_members_of_their_list() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_list(&app, "weekly-digest", "Weekly digest").await?;
+    let link = subscribe_to(&app, "reader@example.com", "newsletter").await?;
+    reqwest::get(link).await?.error_for_status()?;
+    let link = subscribe_to(&app, "digest@example.com", "weekly-digest").await?;
+    reqwest::get(link).await?.error_for_status()?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_newsletters(&app, newsletter_body("weekly-digest")).await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body)?;
+    assert_eq!(body["to"], "digest@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = post_newsletters(&app, newsletter_body("nope")).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "unknown_list");
+    Ok(())
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_of_an_issue_only_leaves_its_list() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_list(&app, "weekly-digest", "Weekly digest").await?;
+    let email = "ursula@example.com";
+    for list in ["newsletter", "weekly-digest"] {
+        let link = subscribe_to(&app, email, list).await?;
+        reqwest::get(link).await?.error_for_status()?;
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+    post_newsletters(&app, newsletter_body("weekly-digest"))
+        .await?
+        .error_for_status()?;
+    dispatch_all_pending_emails(&app).await?;
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body)?;
+    let header = body["headers"]["List-Unsubscribe"].as_str().unwrap();
+    let unsubscribe_link = get_link(&app, header.trim_matches(['<', '>']))?;
+
+    // Act
+    reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // Assert
+    assert_eq!(
+        membership_status(&app, email, "weekly-digest")
+            .await?
+            .as_deref(),
+        Some("unsubscribed")
+    );
+    assert_eq!(
+        membership_status(&app, email, "newsletter")
+            .await?
+            .as_deref(),
+        Some("confirmed")
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn an_error_flash_message_is_set_on_failure() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+
+    // Act - Part 1 - Try to login
+    let response = app.post_login(&login_body).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await?;
+    assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+
+    // Act - Part 3 - Reload the login page, the message is only shown once
+    let html_page = app.get_login_html().await?;
+    assert!(!html_page.contains("Authentication failed"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirect_to_admin_dashboard_after_login_success() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act - Part 1 - Login
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_admin_dashboard_html().await?;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_cookie_is_signed_and_http_only() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await?;
+
+    // Assert
+    let cookie = response
+        .cookies()
+        .find(|c| c.name() == "id")
+        .expect("No session cookie was set");
+    assert!(cookie.http_only());
+    // the session key is stored server side, the cookie carries it along with its signature
+    let n_sessions = sqlx::query!("SELECT COUNT(*) AS count FROM sessions")
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_sessions, Some(1));
+    Ok(())
+}
@@ -1,3 +1,22 @@
+mod admin_dashboard;
+mod admin_subscribers;
+mod api_keys;
+mod change_password;
+mod email_domains;
+mod exports;
 mod health_check;
 mod helpers;
+mod imports;
+mod lists;
+mod login;
+mod migrations;
+mod newsletters;
+mod password_reset;
+mod roles;
+mod segments;
+mod subscribers_api;
 mod subscriptions;
+mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod tags;
+mod two_factor;
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use sqlx::{Connection, PgConnection, PgPool};
+use uuid::Uuid;
+use zero2prod::configuration::get_configuration;
+
+/// Creates an empty database and applies the migrations older than `version`
+async fn migrate_until(version: i64) -> Result<PgPool> {
+    let mut config = get_configuration()?;
+    config.database.database_name = Uuid::new_v4().to_string();
+    let mut connection =
+        PgConnection::connect_with(&config.database.postgres_connection_options()).await?;
+    sqlx::query(&format!(
+        r#"CREATE DATABASE "{}";"#,
+        config.database.database_name
+    ))
+    .execute(&mut connection)
+    .await?;
+    connection.close().await?;
+
+    let pool = PgPool::connect_with(config.database.connection_options()).await?;
+    let mut migrator = sqlx::migrate!("./migrations");
+    migrator.migrations = migrator
+        .migrations
+        .iter()
+        .filter(|migration| migration.version < version)
+        .cloned()
+        .collect();
+    migrator.run(&pool).await?;
+    Ok(pool)
+}
+
+async fn insert_subscriber(
+    pool: &PgPool,
+    email: &str,
+    status: &str,
+    age_days: i32,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, 'name', now() - make_interval(days => $3), $4, $5)
+        "#,
+    )
+    .bind(id)
+    .bind(email)
+    .bind(age_days)
+    .bind(status)
+    .bind(Uuid::new_v4().to_string())
+    .execute(pool)
+    .await?;
+    Ok(id)
+}
+
+#[tokio::test]
+async fn subscribers_differing_only_by_case_are_merged() -> Result<()> {
+    // Arrange
+    let pool = migrate_until(20261018203527).await?;
+    let oldest = insert_subscriber(&pool, "ursula@example.com", "pending_confirmation", 3).await?;
+    let confirmed = insert_subscriber(&pool, "Ursula@Example.com", "confirmed", 2).await?;
+    let newest = insert_subscriber(&pool, "URSULA@example.COM", "pending_confirmation", 1).await?;
+    let pending =
+        insert_subscriber(&pool, "le.guin@example.com", "pending_confirmation", 2).await?;
+    let newer = insert_subscriber(&pool, "le.guin@EXAMPLE.com", "pending_confirmation", 1).await?;
+    sqlx::query(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ('token', $1)",
+    )
+    .bind(newer)
+    .execute(&pool)
+    .await?;
+    let issue_id = Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO newsletter_issues (newsletter_issue_id, title, text_content, html_content, published_at)
+        VALUES ($1, 'title', 'text', 'html', now())
+        "#,
+    )
+    .bind(issue_id)
+    .execute(&pool)
+    .await?;
+    for subscriber_id in [oldest, confirmed, newest] {
+        sqlx::query(
+            "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_id) VALUES ($1, $2)",
+        )
+        .bind(issue_id)
+        .bind(subscriber_id)
+        .execute(&pool)
+        .await?;
+    }
+
+    // Act
+    sqlx::migrate!("./migrations").run(&pool).await?;
+
+    // Assert
+    let mut ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM subscriptions")
+        .fetch_all(&pool)
+        .await?;
+    ids.sort();
+    let mut expected = vec![confirmed, pending];
+    expected.sort();
+    assert_eq!(ids, expected);
+    let token_owner: Uuid = sqlx::query_scalar(
+        "SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = 'token'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(token_owner, pending);
+    let queued: Vec<Uuid> = sqlx::query_scalar("SELECT subscriber_id FROM issue_delivery_queue")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(queued, vec![confirmed]);
+    let memberships: i64 = sqlx::query_scalar("SELECT count(*) FROM list_memberships")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(memberships, 2);
+    Ok(())
+}
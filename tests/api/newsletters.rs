@@ -0,0 +1,411 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, dispatch_all_pending_emails,
+    post_newsletters, post_newsletters_with_idempotency_key, spawn_app,
+};
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_unconfirmed_subscriber(&app).await?;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        // we assert that no request is fired at the email API
+        .expect(0)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    let response = post_newsletters(&app, newsletter_request_body).await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    // mock verifies on drop that we haven't sent the newsletter email
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_to_confirmed_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    let response = post_newsletters(&app, newsletter_request_body).await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let email_request = &app.email_server().received_requests().await.unwrap()[1];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body)?;
+    assert!(
+        body["headers"]["List-Unsubscribe"]
+            .as_str()
+            .unwrap()
+            .contains("/subscriptions/unsubscribe?unsubscribe_token=")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletters_skip_subscribers_with_invalid_stored_emails() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+    sqlx::query!("UPDATE subscriptions SET email = 'definitely-not-an-email'",)
+        .execute(&app.pool)
+        .await?;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    let response = post_newsletters(&app, newsletter_request_body).await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_kept_in_the_queue_for_a_retry() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(app.config.email_client.retry.max_attempts as u64)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    post_newsletters(&app, newsletter_request_body)
+        .await?
+        .error_for_status()?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    let task = sqlx::query!("SELECT n_retries, execute_after FROM issue_delivery_queue",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(task.n_retries, 1);
+    assert!(task.execute_after > chrono::Utc::now());
+    Ok(())
+}
+
+#[tokio::test]
+async fn permanently_rejected_deliveries_are_not_retried() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    post_newsletters(&app, newsletter_request_body)
+        .await?
+        .error_for_status()?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    let n_tasks = sqlx::query!("SELECT COUNT(*) AS count FROM issue_delivery_queue",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(n_tasks.count, Some(0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_invalid_data() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "content": {
+                    "text": "Newsletter body as plain text",
+                    "html": "<p>Newsletter body as HTML</p>",
+                }
+            }),
+            "missing title",
+        ),
+        (
+            serde_json::json!({"title": "Newsletter!"}),
+            "missing content",
+        ),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        // Act
+        let response = post_newsletters(&app, invalid_body).await?;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            error_message
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletter_creation_is_idempotent() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act - publish the same issue twice
+    let first_response = post_newsletters_with_idempotency_key(
+        &app,
+        newsletter_request_body.clone(),
+        &idempotency_key,
+    )
+    .await?;
+    let second_response =
+        post_newsletters_with_idempotency_key(&app, newsletter_request_body, &idempotency_key)
+            .await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(first_response.status().as_u16(), 202);
+    assert_eq!(second_response.status().as_u16(), 202);
+    let n_issues = sqlx::query!("SELECT COUNT(*) AS count FROM newsletter_issues",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(n_issues.count, Some(1));
+    // mock verifies on drop that we have sent the newsletter email once
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_newsletter_creation_is_handled_gracefully() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act - submit two requests concurrently
+    let (first_response, second_response) = tokio::join!(
+        post_newsletters_with_idempotency_key(
+            &app,
+            newsletter_request_body.clone(),
+            &idempotency_key
+        ),
+        post_newsletters_with_idempotency_key(&app, newsletter_request_body, &idempotency_key),
+    );
+    let (first_response, second_response) = (first_response?, second_response?);
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.bytes().await?,
+        second_response.bytes().await?
+    );
+    // mock verifies on drop that we have sent the newsletter email once
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_an_invalid_idempotency_key() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+
+    // Act
+    let response = post_newsletters_with_idempotency_key(&app, newsletter_request_body, "").await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_missing_authorization_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .send()
+        .await?;
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn non_existing_user_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    // random credentials
+    let username = uuid::Uuid::new_v4().to_string();
+    let password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .send()
+        .await?;
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_password_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let username = &app.test_user.username;
+    // random password
+    let password = uuid::Uuid::new_v4().to_string();
+    assert_ne!(app.test_user.password, password);
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.config.app_address()))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .send()
+        .await?;
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::Url;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, get_link, spawn_app};
+
+const NEW_PASSWORD: &str = "a-new-password-with-digits-1234";
+
+/// Requests a reset for the test user and returns the link from the email
+async fn request_reset_link(app: &TestApp) -> Result<Url> {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(app.email_server())
+        .await;
+
+    let response = app.post_forgot_password(&app.test_user.email).await?;
+    assert_is_redirect_to(&response, "/password/forgot");
+
+    let email_request = wait_for_email(app).await?;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body)?;
+    get_link(app, body["text"].as_str().unwrap())
+}
+
+/// The email is sent in the background, after the response
+async fn wait_for_email(app: &TestApp) -> Result<wiremock::Request> {
+    for _ in 0..100 {
+        if let Some(request) = app.email_server().received_requests().await.unwrap().pop() {
+            return Ok(request);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    anyhow::bail!("No password reset email was sent")
+}
+
+fn token_from(link: &Url) -> String {
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+async fn reset_password(app: &TestApp, token: &str) -> Result<reqwest::Response> {
+    app.post_reset_password(&serde_json::json!({
+        "token": token,
+        "new_password": NEW_PASSWORD,
+        "new_password_check": NEW_PASSWORD,
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn unknown_emails_get_the_same_answer_without_an_email_being_sent() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = app.post_forgot_password("nobody@example.com").await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password/forgot");
+    let html_page = app.get_page("/password/forgot").await?.text().await?;
+    assert!(html_page.contains("If an account with that email address exists"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn failing_to_send_the_email_does_not_change_the_answer() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = app.post_forgot_password(&app.test_user.email).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password/forgot");
+    Ok(())
+}
+
+#[tokio::test]
+async fn emails_are_matched_regardless_of_case() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = app
+        .post_forgot_password(&app.test_user.email.to_uppercase())
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password/forgot");
+    let email_request = wait_for_email(&app).await?;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body)?;
+    assert_eq!(body["to"], app.test_user.email);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reset_tokens_are_hashed_at_rest() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let link = request_reset_link(&app).await?;
+
+    // Assert
+    let stored = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_ne!(stored.token_hash, token_from(&link));
+    Ok(())
+}
+
+#[tokio::test]
+async fn the_reset_link_lets_the_user_choose_a_new_password() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let link = request_reset_link(&app).await?;
+
+    // Act - Part 1 - Open the link
+    let form = app.api_client.get(link.clone()).send().await?;
+    assert_eq!(form.status().as_u16(), 200);
+
+    // Act - Part 2 - Submit a new password
+    let response = reset_password(&app, &token_from(&link)).await?;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Login using the new password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD,
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    Ok(())
+}
+
+#[tokio::test]
+async fn resetting_the_password_ends_existing_sessions() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let other_device = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()?;
+    let response = other_device
+        .post(format!("{}/login", app.config.app_address()))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await?;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let link = request_reset_link(&app).await?;
+
+    // Act
+    let response = reset_password(&app, &token_from(&link)).await?;
+    assert_is_redirect_to(&response, "/login");
+
+    // Assert
+    let response = other_device
+        .get(format!("{}/admin/dashboard", app.config.app_address()))
+        .send()
+        .await?;
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn reset_tokens_are_single_use() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let link = request_reset_link(&app).await?;
+    reset_password(&app, &token_from(&link)).await?;
+
+    // Act
+    let response = reset_password(&app, &token_from(&link)).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password/forgot");
+    let html_page = app.get_page("/password/forgot").await?.text().await?;
+    assert!(html_page.contains("The password reset link is invalid or has expired."));
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_reset_tokens_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let link = request_reset_link(&app).await?;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.pool)
+        .await?;
+
+    // Act
+    let response = reset_password(&app, &token_from(&link)).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password/forgot");
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_weak_new_password_sends_the_user_back_to_the_reset_form() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let link = request_reset_link(&app).await?;
+    let token = token_from(&link);
+
+    // Act
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": "short1",
+            "new_password_check": "short1",
+        }))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/password/reset?token={token}"));
+    // the token wasn't used up by the failed attempt
+    let response = reset_password(&app, &token).await?;
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
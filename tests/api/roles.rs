@@ -0,0 +1,218 @@
+use anyhow::Result;
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+use zero2prod::authentication::Role;
+
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, post_newsletters_as, spawn_app};
+
+/// Stores a user with the given role and logs in as them
+async fn login_with_role(app: &TestApp, role: Role) -> Result<TestUser> {
+    let user = TestUser::generate_with_role(role);
+    user.store(&app.pool).await?;
+    app.login_as(&user).await?;
+    Ok(user)
+}
+
+async fn insert_subscriber(app: &TestApp) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, 'a@example.com', 'name', now(), 'confirmed', $2)
+        "#,
+        id,
+        Uuid::new_v4().to_string(),
+    )
+    .execute(&app.pool)
+    .await?;
+    Ok(id)
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+#[tokio::test]
+async fn viewers_can_browse_but_not_change_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let subscriber_id = insert_subscriber(&app).await?;
+    login_with_role(&app, Role::Viewer).await?;
+
+    // Act
+    let dashboard = app.get_admin_dashboard().await?;
+    let list = app.get_page("/admin/subscribers").await?;
+    let delete = app
+        .post_admin_action(&format!("/admin/subscribers/{subscriber_id}/delete"))
+        .await?;
+
+    // Assert
+    assert_eq!(dashboard.status().as_u16(), 200);
+    assert_eq!(list.status().as_u16(), 200);
+    assert_eq!(delete.status().as_u16(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn editors_can_manage_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let subscriber_id = insert_subscriber(&app).await?;
+    login_with_role(&app, Role::Editor).await?;
+
+    // Act
+    let response = app
+        .post_admin_action(&format!("/admin/subscribers/{subscriber_id}/unsubscribe"))
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_publishers_and_owners_can_publish() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+
+    for (role, expected_status) in [
+        (Role::Viewer, 403),
+        (Role::Editor, 403),
+        (Role::Publisher, 202),
+        (Role::Owner, 202),
+    ] {
+        let user = TestUser::generate_with_role(role);
+        user.store(&app.pool).await?;
+
+        // Act
+        let response = post_newsletters_as(&app, &user, newsletter_body()).await?;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "Unexpected status for a {}",
+            role
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let other = TestUser::generate_with_role(Role::Viewer);
+    other.store(&app.pool).await?;
+    login_with_role(&app, Role::Publisher).await?;
+
+    // Act
+    let list = app.get_page("/admin/users").await?;
+    let promote = app
+        .post_admin_form(
+            &format!("/admin/users/{}/role", other.user_id),
+            &serde_json::json!({ "role": "owner" }),
+        )
+        .await?;
+
+    // Assert
+    assert_eq!(list.status().as_u16(), 403);
+    assert_eq!(promote.status().as_u16(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn owners_can_add_users_and_change_their_roles() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let password = "a-password-with-digits-1234";
+
+    // Act - Part 1 - Add a viewer
+    let response = app
+        .post_admin_form(
+            "/admin/users",
+            &serde_json::json!({
+                "username": "new-user",
+                "email": "",
+                "role": "viewer",
+                "password": password,
+                "password_check": password,
+            }),
+        )
+        .await?;
+    assert_is_redirect_to(&response, "/admin/users");
+    let user_id = sqlx::query!("SELECT user_id FROM users WHERE username = 'new-user'")
+        .fetch_one(&app.pool)
+        .await?
+        .user_id;
+
+    // Act - Part 2 - Promote them
+    let response = app
+        .post_admin_form(
+            &format!("/admin/users/{user_id}/role"),
+            &serde_json::json!({ "role": "editor" }),
+        )
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let role = sqlx::query!("SELECT role FROM users WHERE user_id = $1", user_id)
+        .fetch_one(&app.pool)
+        .await?
+        .role;
+    assert_eq!(role, "editor");
+    Ok(())
+}
+
+#[tokio::test]
+async fn owners_cannot_demote_or_delete_themselves() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let own_id = app.test_user.user_id;
+
+    // Act
+    app.post_admin_form(
+        &format!("/admin/users/{own_id}/role"),
+        &serde_json::json!({ "role": "viewer" }),
+    )
+    .await?;
+    app.post_admin_action(&format!("/admin/users/{own_id}/delete"))
+        .await?;
+
+    // Assert
+    let role = sqlx::query!("SELECT role FROM users WHERE user_id = $1", own_id)
+        .fetch_one(&app.pool)
+        .await?
+        .role;
+    assert_eq!(role, "owner");
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleted_users_are_logged_out() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let user = login_with_role(&app, Role::Editor).await?;
+
+    // Act
+    sqlx::query!("DELETE FROM users WHERE user_id = $1", user.user_id)
+        .execute(&app.pool)
+        .await?;
+    let response = app.get_admin_dashboard().await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
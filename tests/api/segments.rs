@@ -0,0 +1,211 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero2prod::domain::{AttributeDefinition, AttributeKind};
+
+use crate::helpers::{
+    TestApp, dispatch_all_pending_emails, get_confirmation_links, post_newsletters,
+    post_subscriptions, spawn_app_with,
+};
+
+async fn spawn_app_with_attributes() -> Result<TestApp> {
+    spawn_app_with(|config| {
+        config.subscriptions.attributes = [
+            ("country", AttributeKind::Text),
+            ("age", AttributeKind::Number),
+            ("vip", AttributeKind::Boolean),
+        ]
+        .into_iter()
+        .map(|(name, kind)| AttributeDefinition {
+            name: name.to_string(),
+            kind,
+        })
+        .collect();
+    })
+    .await
+}
+
+/// Signs up and confirms a subscriber with the given extra form fields
+async fn create_confirmed_subscriber_with(app: &TestApp, email: &str, fields: &str) -> Result<()> {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(app.email_server())
+        .await;
+    post_subscriptions(app, format!("name=le%20guin&email={email}&{fields}"))
+        .await?
+        .error_for_status()?;
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let link = get_confirmation_links(app, &email_request.unwrap())?.html;
+    reqwest::get(link).await?.error_for_status()?;
+    Ok(())
+}
+
+fn newsletter_body(segment: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        },
+        "segment": segment,
+    })
+}
+
+#[tokio::test]
+async fn attributes_given_on_signup_are_stored_with_their_type() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40example.com&country=AU&age=42&vip=&submit=Go".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT attributes FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(
+        saved.attributes,
+        serde_json::json!({ "country": "AU", "age": 42.0 })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn signing_up_again_does_not_change_stored_attributes() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+    create_confirmed_subscriber_with(&app, "ursula%40example.com", "country=AU").await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40example.com&country=US&vip=true".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status, attributes FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    assert_eq!(saved.attributes, serde_json::json!({ "country": "AU" }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_attributes_are_reported_per_field() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula%40example.com&age=old".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["errors"][0]["field"], "attributes.age");
+    assert_eq!(problem["errors"][0]["code"], "attribute_invalid");
+    Ok(())
+}
+
+#[tokio::test]
+async fn issues_targeting_a_segment_only_reach_matching_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+    create_confirmed_subscriber_with(&app, "au@example.com", "country=AU&age=30").await?;
+    create_confirmed_subscriber_with(&app, "young@example.com", "country=AU&age=15").await?;
+    create_confirmed_subscriber_with(&app, "nz@example.com", "country=NZ&age=30").await?;
+    create_confirmed_subscriber_with(&app, "unknown@example.com", "").await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_newsletters(
+        &app,
+        newsletter_body(r#"country = "AU" and age >= 18 and signed_up_after 2000-01-01"#),
+    )
+    .await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body)?;
+    assert_eq!(body["to"], "au@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn negated_conditions_include_subscribers_without_the_attribute() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+    create_confirmed_subscriber_with(&app, "vip@example.com", "vip=true").await?;
+    create_confirmed_subscriber_with(&app, "regular@example.com", "").await?;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    post_newsletters(&app, newsletter_body("not vip = true"))
+        .await?
+        .error_for_status()?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body)?;
+    assert_eq!(body["to"], "regular@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_segments_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_attributes().await?;
+
+    // Act
+    let response = post_newsletters(&app, newsletter_body("shoe_size > 9")).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "invalid_segment");
+    assert_eq!(
+        problem["detail"],
+        "Invalid segment at position 0: There is no field called shoe_size."
+    );
+    Ok(())
+}
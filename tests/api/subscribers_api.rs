@@ -0,0 +1,180 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, spawn_app};
+
+async fn post_api_subscribers(
+    app: &TestApp,
+    api_key: &str,
+    body: serde_json::Value,
+) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}/api/v1/subscribers", app.config.app_address()))
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await?)
+}
+
+async fn subscribers_write_key(app: &TestApp) -> Result<String> {
+    app.login().await?;
+    app.create_api_key(&["subscribers:write"]).await
+}
+
+#[tokio::test]
+async fn valid_json_creates_a_pending_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_api_subscribers(
+        &app,
+        &key,
+        serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["status"], "pending_confirmation");
+    assert!(body["subscribed_at"].is_string());
+    let saved = sqlx::query!("SELECT id, email, status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(body["id"], saved.id.to_string());
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "pending_confirmation");
+    Ok(())
+}
+
+#[tokio::test]
+async fn every_invalid_field_is_reported() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "name": "", "email": "not-an-email" }),
+            vec!["name", "email"],
+        ),
+        (serde_json::json!({ "name": "le guin" }), vec!["email"]),
+        (
+            serde_json::json!({ "email": "ursula_le_guin@gmail.com" }),
+            vec!["name"],
+        ),
+        // no attributes are configured
+        (
+            serde_json::json!({
+                "name": "le guin",
+                "email": "ursula_le_guin@gmail.com",
+                "attributes": { "country": "AU" },
+            }),
+            vec!["attributes.country"],
+        ),
+    ];
+
+    for (body, invalid_fields) in test_cases {
+        // Act
+        let response = post_api_subscribers(&app, &key, body.clone()).await?;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "Payload: {}", body);
+        let errors: serde_json::Value = response.json().await?;
+        let fields: Vec<&str> = errors["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, invalid_fields, "Payload: {}", body);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn creating_subscribers_requires_the_subscribers_write_scope() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let key = app.create_api_key(&["stats:read"]).await?;
+
+    // Act
+    let response = post_api_subscribers(
+        &app,
+        &key,
+        serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn keys_stop_working_once_their_creator_is_demoted() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    sqlx::query!(
+        "UPDATE users SET role = 'viewer' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.pool)
+    .await?;
+
+    // Act
+    let response = post_api_subscribers(
+        &app,
+        &key,
+        serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "insufficient_role");
+    let n_subscribers = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_subscribers, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn known_addresses_return_the_existing_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+    let body = serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" });
+
+    // Act
+    let first = post_api_subscribers(&app, &key, body.clone()).await?;
+    let second = post_api_subscribers(&app, &key, body).await?;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 201);
+    assert_eq!(second.status().as_u16(), 200);
+    let first: serde_json::Value = first.json().await?;
+    let second: serde_json::Value = second.json().await?;
+    assert_eq!(first["id"], second["id"]);
+    Ok(())
+}
@@ -1,12 +1,23 @@
 use anyhow::{Ok, Result};
-use zero2prod::spawn_test_app;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
 
-use crate::helpers::post_subscriptions;
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, get_confirmation_links,
+    post_subscriptions, spawn_app,
+};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() -> Result<()> {
     // Arrange
-    let app = spawn_test_app().await?;
+    let app = spawn_app().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
 
     // Act
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
@@ -14,19 +25,38 @@ async fn subscribe_returns_a_200_for_valid_form_data() -> Result<()> {
 
     // Assert
     assert_eq!(200, response.status().as_u16());
-    let saved = sqlx::query!("SELECT email, name FROM subscriptions",)
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_persists_the_new_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
         .fetch_one(&app.pool)
         .await?;
 
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, "pending_confirmation");
     Ok(())
 }
 
 #[tokio::test]
 async fn subscribe_returns_a_400_when_data_is_missing() -> Result<()> {
     // Arrange
-    let app = spawn_test_app().await?;
+    let app = spawn_app().await?;
     let test_cases = vec![
         ("name=le%20guin", "missing the email"),
         ("email=ursula_le_guin%40gmail.com", "missing the name"),
@@ -52,7 +82,7 @@ async fn subscribe_returns_a_400_when_data_is_missing() -> Result<()> {
 #[tokio::test]
 async fn subscribe_returns_a_400_when_fields_are_present_but_empty() -> Result<()> {
     // Arrange
-    let app = spawn_test_app().await?;
+    let app = spawn_app().await?;
     let test_cases = vec![
         ("name=&email=ursula_le_guin%40gmail.com", "empty name"),
         ("name=Ursula&email=", "empty email"),
@@ -74,3 +104,227 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_empty() -> Result<(
 
     Ok(())
 }
+
+#[tokio::test]
+async fn subscribe_describes_invalid_fields_as_problem_details() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = post_subscriptions(
+        &app,
+        "name=Ursula%20(Le%20Guin)&email=not-an-email".to_string(),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["code"], "validation_failed");
+    assert_eq!(problem["errors"][0]["field"], "name");
+    assert_eq!(problem["errors"][0]["code"], "name_forbidden_character");
+    assert_eq!(problem["errors"][1]["field"], "email");
+    assert_eq!(problem["errors"][1]["code"], "email_invalid");
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_sends_a_confirmation_email_with_a_link() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    let email_request = &app.email_server().received_requests().await.unwrap()[0];
+    let confirmation_links = get_confirmation_links(&app, email_request)?;
+
+    // the two links should be identical
+    assert_eq!(confirmation_links.html, confirmation_links.plain_text);
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_fails_if_there_is_a_fatal_database_error() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    // sabotage the database
+    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token;",)
+        .execute(&app.pool)
+        .await?;
+
+    // Act
+    let response = post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    assert_eq!(500, response.status().as_u16());
+    // the cause only goes to the logs
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "internal_error");
+    assert!(problem.get("detail").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribing_twice_while_pending_resends_the_same_link() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let first = post_subscriptions(&app, body.to_string()).await?;
+    let second = post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, second.status().as_u16());
+    let requests = app.email_server().received_requests().await.unwrap();
+    let first_links = get_confirmation_links(&app, &requests[0])?;
+    let second_links = get_confirmation_links(&app, &requests[1])?;
+    assert_eq!(first_links.html, second_links.html);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_subscribers, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_looks_like_a_new_signup() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    create_confirmed_subscriber(&app).await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.text().await?.is_empty());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribing_again_after_unsubscribing_starts_a_fresh_opt_in() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let old_links = create_unconfirmed_subscriber(&app).await?;
+    reqwest::get(old_links.html.clone())
+        .await?
+        .error_for_status()?;
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?
+        .unsubscribe_token;
+    reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            app.config.app_address(),
+            unsubscribe_token
+        ))
+        .send()
+        .await?
+        .error_for_status()?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = post_subscriptions(&app, body.to_string()).await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "pending_confirmation");
+    assert!(saved.unsubscribed_at.is_none());
+
+    let email_request = app
+        .email_server()
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let new_links = get_confirmation_links(&app, &email_request)?;
+    assert_ne!(old_links.html, new_links.html);
+    assert_eq!(reqwest::get(old_links.html).await?.status().as_u16(), 401);
+    reqwest::get(new_links.html).await?.error_for_status()?;
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn addresses_differing_only_in_case_are_the_same_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    post_subscriptions(
+        &app,
+        "name=le%20guin&email=%20Ursula_Le_Guin%40Gmail.COM%20".to_string(),
+    )
+    .await?
+    .error_for_status()?;
+    post_subscriptions(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com".to_string(),
+    )
+    .await?
+    .error_for_status()?;
+
+    // Assert
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.pool)
+        .await?;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "Ursula_Le_Guin@gmail.com");
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    get_confirmation_links, get_link, latest_email_for, post_subscriptions, spawn_app,
+    spawn_app_with_outbox,
+};
+
+#[tokio::test]
+async fn confirmations_without_token_are_rejected_with_a_400() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm",
+        app.config.app_address()
+    ))
+    .await?;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirmations_with_an_unknown_token_are_rejected_with_a_401() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=notarealtoken",
+        app.config.app_address()
+    ))
+    .await?;
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn clicking_on_the_confirmation_link_confirms_a_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+
+    post_subscriptions(&app, body.to_string()).await?;
+    let email_request = &app.email_server().received_requests().await.unwrap()[0];
+    let confirmation_links = get_confirmation_links(&app, email_request)?;
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn the_confirmation_email_written_to_the_outbox_confirms_a_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app_with_outbox().await?;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    post_subscriptions(&app, body.to_string())
+        .await?
+        .error_for_status()?;
+
+    let email = latest_email_for(&app, "ursula_le_guin@gmail.com")?;
+    assert_eq!(email.subject, "Welcome!");
+    let confirmation_link = get_link(&app, &email.text)?;
+
+    // Act
+    let response = reqwest::get(confirmation_link).await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "confirmed");
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{TestApp, post_subscriptions, spawn_app};
+
+async fn create_subscriber(app: &TestApp) -> Result<String> {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(app.email_server())
+        .await;
+    post_subscriptions(app, body.to_string())
+        .await?
+        .error_for_status()?;
+
+    let saved = sqlx::query!("SELECT unsubscribe_token FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+    Ok(saved.unsubscribe_token)
+}
+
+#[tokio::test]
+async fn unsubscribe_without_token_is_rejected_with_a_400() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe",
+        app.config.app_address()
+    ))
+    .await?;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsubscribe_with_an_unknown_token_is_rejected_with_a_401() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token=notarealtoken",
+        app.config.app_address()
+    ))
+    .await?;
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn following_the_unsubscribe_link_only_asks_for_confirmation() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let token = create_subscriber(&app).await?;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}&list=newsletter",
+        app.config.app_address(),
+        token
+    ))
+    .await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let html_page = response.text().await?;
+    assert!(html_page.contains(&format!(
+        r#"<form action="/subscriptions/unsubscribe?unsubscribe_token={}&amp;list=newsletter" method="post">"#,
+        token
+    )));
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "pending_confirmation");
+    assert!(saved.unsubscribed_at.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirming_the_unsubscription_unsubscribes_a_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let token = create_subscriber(&app).await?;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            app.config.app_address(),
+            token
+        ))
+        .send()
+        .await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "unsubscribed");
+    assert!(saved.unsubscribed_at.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_post_unsubscribes_a_subscriber() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let token = create_subscriber(&app).await?;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            app.config.app_address(),
+            token
+        ))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await?;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions",)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, "unsubscribed");
+    Ok(())
+}
@@ -0,0 +1,358 @@
+use anyhow::Result;
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_is_redirect_to, dispatch_all_pending_emails, get_confirmation_links,
+    post_newsletters, post_subscriptions, spawn_app,
+};
+
+/// Signs up a subscriber, confirming them if asked, and returns their id
+async fn create_subscriber(app: &TestApp, email: &str, confirm: bool) -> Result<Uuid> {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(app.email_server())
+        .await;
+    post_subscriptions(
+        app,
+        format!("name=le%20guin&email={}", email.replace('@', "%40")),
+    )
+    .await?
+    .error_for_status()?;
+    if confirm {
+        let email_request = app.email_server().received_requests().await.unwrap().pop();
+        let link = get_confirmation_links(app, &email_request.unwrap())?.html;
+        reqwest::get(link).await?.error_for_status()?;
+    }
+    let saved = sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.pool)
+        .await?;
+    Ok(saved.id)
+}
+
+async fn get_tags(app: &TestApp, subscriber_id: Uuid) -> Result<Vec<String>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT t.name
+        FROM subscriber_tags st
+        JOIN tags t ON t.tag_id = st.tag_id
+        WHERE st.subscriber_id = $1
+        ORDER BY t.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(&app.pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| r.name).collect())
+}
+
+async fn subscribers_write_key(app: &TestApp) -> Result<String> {
+    app.login().await?;
+    app.create_api_key(&["subscribers:write"]).await
+}
+
+async fn post_api(
+    app: &TestApp,
+    api_key: &str,
+    path: &str,
+    body: serde_json::Value,
+) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}{}", app.config.app_address(), path))
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await?)
+}
+
+#[tokio::test]
+async fn api_clients_can_add_and_remove_tags() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    let subscriber_id = create_subscriber(&app, "ursula@example.com", true).await?;
+    let tags_path = format!("/api/v1/subscribers/{}/tags", subscriber_id);
+
+    // Act - Part 1 - Add
+    let response = post_api(
+        &app,
+        &key,
+        &tags_path,
+        serde_json::json!({ "tags": ["Beta", "conference-2026"] }),
+    )
+    .await?;
+
+    // Assert - Part 1
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["tags"], serde_json::json!(["beta", "conference-2026"]));
+
+    // Act - Part 2 - Remove
+    let response = reqwest::Client::new()
+        .delete(format!("{}{}/beta", app.config.app_address(), tags_path))
+        .bearer_auth(&key)
+        .send()
+        .await?;
+
+    // Assert - Part 2
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(get_tags(&app, subscriber_id).await?, ["conference-2026"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tagging_an_unknown_subscriber_returns_404() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+
+    // Act
+    let response = post_api(
+        &app,
+        &key,
+        &format!("/api/v1/subscribers/{}/tags", Uuid::new_v4()),
+        serde_json::json!({ "tags": ["beta"] }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "unknown_subscriber");
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_tags_are_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    let subscriber_id = create_subscriber(&app, "ursula@example.com", true).await?;
+
+    // Act
+    let response = post_api(
+        &app,
+        &key,
+        &format!("/api/v1/subscribers/{}/tags", subscriber_id),
+        serde_json::json!({ "tags": ["paying customer"] }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "tag_invalid_characters");
+    assert!(get_tags(&app, subscriber_id).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn bulk_tagging_only_applies_to_matching_subscribers() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    let confirmed = create_subscriber(&app, "confirmed@example.com", true).await?;
+    let pending = create_subscriber(&app, "pending@example.com", false).await?;
+
+    // Act - Part 1 - Add
+    let response = post_api(
+        &app,
+        &key,
+        "/api/v1/tags/bulk",
+        serde_json::json!({
+            "action": "add",
+            "tags": ["active", "beta"],
+            "filter": { "status": "confirmed" },
+        }),
+    )
+    .await?;
+
+    // Assert - Part 1
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["affected"], 2);
+    assert_eq!(get_tags(&app, confirmed).await?, ["active", "beta"]);
+    assert!(get_tags(&app, pending).await?.is_empty());
+
+    // Act - Part 2 - Remove from everyone tagged active
+    let response = post_api(
+        &app,
+        &key,
+        "/api/v1/tags/bulk",
+        serde_json::json!({
+            "action": "remove",
+            "tags": ["beta"],
+            "filter": { "include_tags": ["active"] },
+        }),
+    )
+    .await?;
+
+    // Assert - Part 2
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["affected"], 1);
+    assert_eq!(get_tags(&app, confirmed).await?, ["active"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn bulk_removing_an_unknown_tag_does_not_create_it() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    create_subscriber(&app, "confirmed@example.com", true).await?;
+
+    // Act
+    let response = post_api(
+        &app,
+        &key,
+        "/api/v1/tags/bulk",
+        serde_json::json!({
+            "action": "remove",
+            "tags": ["betta"],
+            "filter": {},
+        }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["affected"], 0);
+    let n_tags = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM tags"#)
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_tags, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn bulk_tagging_with_an_unknown_list_is_rejected() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+
+    // Act
+    let response = post_api(
+        &app,
+        &key,
+        "/api/v1/tags/bulk",
+        serde_json::json!({
+            "action": "add",
+            "tags": ["beta"],
+            "filter": { "list": "weekly-digest" },
+        }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "unknown_list");
+    Ok(())
+}
+
+#[tokio::test]
+async fn editors_can_tag_subscribers_from_the_admin_pages() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let subscriber_id = create_subscriber(&app, "ursula@example.com", true).await?;
+    let subscriber_path = format!("/admin/subscribers/{}", subscriber_id);
+
+    // Act - Part 1 - Add from the subscriber page
+    let response = app
+        .post_admin_form(
+            &format!("{}/tags", subscriber_path),
+            &[("tags", "beta, vip")],
+        )
+        .await?;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, &subscriber_path);
+    let html = app.get_page(&subscriber_path).await?.text().await?;
+    assert!(html.contains("The tags have been added."));
+    assert!(html.contains("<li>beta"));
+    assert!(html.contains("<li>vip"));
+
+    // Act - Part 2 - Remove one
+    let response = app
+        .post_admin_action(&format!("{}/tags/vip/delete", subscriber_path))
+        .await?;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, &subscriber_path);
+    assert_eq!(get_tags(&app, subscriber_id).await?, ["beta"]);
+
+    // Act - Part 3 - Bulk remove from the tags page
+    let response = app
+        .post_admin_form(
+            "/admin/tags/bulk",
+            &[("action", "remove"), ("tags", "beta"), ("status", "")],
+        )
+        .await?;
+
+    // Assert - Part 3
+    assert_is_redirect_to(&response, "/admin/tags");
+    let html = app.get_page("/admin/tags").await?.text().await?;
+    assert!(html.contains("1 tags have been removed."));
+    assert!(get_tags(&app, subscriber_id).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn issues_can_include_and_exclude_tags() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    let key = subscribers_write_key(&app).await?;
+    for (email, tags) in [
+        ("beta@example.com", vec!["beta"]),
+        ("churned@example.com", vec!["beta", "churned"]),
+        ("other@example.com", vec![]),
+    ] {
+        let subscriber_id = create_subscriber(&app, email, true).await?;
+        post_api(
+            &app,
+            &key,
+            &format!("/api/v1/subscribers/{}/tags", subscriber_id),
+            serde_json::json!({ "tags": tags }),
+        )
+        .await?
+        .error_for_status()?;
+    }
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(app.email_server())
+        .await;
+
+    // Act
+    let response = post_newsletters(
+        &app,
+        serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            },
+            "include_tags": ["beta"],
+            "exclude_tags": ["churned"],
+        }),
+    )
+    .await?;
+    dispatch_all_pending_emails(&app).await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let email_request = app.email_server().received_requests().await.unwrap().pop();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.unwrap().body)?;
+    assert_eq!(body["to"], "beta@example.com");
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::helpers::{TestApp, assert_is_redirect_to, post_newsletters, spawn_app};
+
+/// Code for the step `offset` steps away from the current one
+fn totp_code(secret: &str, offset: i64) -> String {
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    totp.generate((now + offset * 30) as u64)
+}
+
+async fn stored_secret(app: &TestApp) -> Result<String> {
+    Ok(sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.pool)
+    .await?
+    .totp_secret
+    .expect("No TOTP secret was stored"))
+}
+
+/// Goes through enrollment as the logged in test user, returning the secret and recovery codes
+async fn enable_two_factor(app: &TestApp) -> Result<(String, Vec<String>)> {
+    let response = app.post_admin_action("/admin/two-factor/enroll").await?;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let secret = stored_secret(app).await?;
+
+    let response = app
+        .post_admin_form(
+            "/admin/two-factor/enable",
+            &serde_json::json!({ "code": totp_code(&secret, -1) }),
+        )
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await?;
+    let recovery_codes = html_page
+        .split("<code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_string())
+        .collect();
+    Ok((secret, recovery_codes))
+}
+
+/// Logs out and back in with the password only, leaving the session waiting for the second step
+async fn log_in_again(app: &TestApp) -> Result<()> {
+    app.post_logout().await?;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await?;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    Ok(())
+}
+
+#[tokio::test]
+async fn enrollment_shows_a_qr_code_and_requires_a_valid_code() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act - Part 1 - Start enrollment
+    app.post_admin_action("/admin/two-factor/enroll").await?;
+    let html_page = app.get_page("/admin/two-factor").await?.text().await?;
+    assert!(html_page.contains("otpauth://totp/"));
+    assert!(html_page.contains("<svg"));
+
+    // Act - Part 2 - Submit a wrong code
+    let response = app
+        .post_admin_form(
+            "/admin/two-factor/enable",
+            &serde_json::json!({ "code": "000000" }),
+        )
+        .await?;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_page("/admin/two-factor").await?.text().await?;
+    assert!(html_page.contains("<p><i>Invalid authentication code.</i></p>"));
+
+    // Act - Part 3 - Submit the right one
+    let secret = stored_secret(&app).await?;
+    let response = app
+        .post_admin_form(
+            "/admin/two-factor/enable",
+            &serde_json::json!({ "code": totp_code(&secret, 0) }),
+        )
+        .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await?.matches("<li><code>").count(), 10);
+    let enabled = sqlx::query!(
+        "SELECT totp_enabled FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.pool)
+    .await?
+    .totp_enabled;
+    assert!(enabled);
+    Ok(())
+}
+
+#[tokio::test]
+async fn recovery_codes_are_hashed_at_rest() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+
+    // Act
+    let (_, recovery_codes) = enable_two_factor(&app).await?;
+
+    // Assert
+    let stored: Vec<String> = sqlx::query!("SELECT code_hash FROM recovery_codes")
+        .fetch_all(&app.pool)
+        .await?
+        .into_iter()
+        .map(|r| r.code_hash)
+        .collect();
+    assert_eq!(stored.len(), recovery_codes.len());
+    for code in &recovery_codes {
+        assert!(!stored.iter().any(|s| s.contains(&code.replace('-', ""))));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_pages_require_the_second_step_once_enabled() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let (secret, _) = enable_two_factor(&app).await?;
+
+    // Act - Part 1 - Password only
+    log_in_again(&app).await?;
+    let response = app.get_admin_dashboard().await?;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    // Act - Part 2 - Second step
+    let response = app.post_two_factor(&totp_code(&secret, 0)).await?;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Assert
+    let response = app.get_admin_dashboard().await?;
+    assert_eq!(response.status().as_u16(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn totp_codes_cannot_be_replayed() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let (secret, _) = enable_two_factor(&app).await?;
+    let code = totp_code(&secret, 0);
+    log_in_again(&app).await?;
+    assert_is_redirect_to(&app.post_two_factor(&code).await?, "/admin/dashboard");
+
+    // Act
+    log_in_again(&app).await?;
+    let response = app.post_two_factor(&code).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    Ok(())
+}
+
+#[tokio::test]
+async fn recovery_codes_can_only_be_used_once() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let (_, recovery_codes) = enable_two_factor(&app).await?;
+
+    // Act - Part 1 - Use a recovery code
+    log_in_again(&app).await?;
+    let response = app
+        .post_two_factor(&recovery_codes[0].to_uppercase())
+        .await?;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act - Part 2 - Use it again
+    log_in_again(&app).await?;
+    let response = app.post_two_factor(&recovery_codes[0]).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    Ok(())
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_end_the_login_attempt() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    enable_two_factor(&app).await?;
+    log_in_again(&app).await?;
+
+    // Act
+    for _ in 0..4 {
+        let response = app.post_two_factor("not-a-code").await?;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = app.post_two_factor("not-a-code").await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_page("/login/two-factor").await?;
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn logging_in_again_does_not_lift_the_lockout() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    let (secret, _) = enable_two_factor(&app).await?;
+    log_in_again(&app).await?;
+    for _ in 0..5 {
+        app.post_two_factor("not-a-code").await?;
+    }
+    log_in_again(&app).await?;
+
+    // Act
+    let response = app.post_two_factor(&totp_code(&secret, 0)).await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_page("/login").await?.text().await?;
+    assert!(html_page.contains("Too many failed attempts"));
+    let response = app.get_page("/admin/dashboard").await?;
+    assert_is_redirect_to(&response, "/login");
+    Ok(())
+}
+
+#[tokio::test]
+async fn disabling_two_factor_requires_the_password() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    enable_two_factor(&app).await?;
+
+    // Act - Part 1 - Wrong password
+    app.post_admin_form(
+        "/admin/two-factor/disable",
+        &serde_json::json!({ "password": "not-the-password" }),
+    )
+    .await?;
+    assert!(stored_secret(&app).await.is_ok());
+
+    // Act - Part 2 - Right password
+    let response = app
+        .post_admin_form(
+            "/admin/two-factor/disable",
+            &serde_json::json!({ "password": &app.test_user.password }),
+        )
+        .await?;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let response = app.post_logout().await?;
+    assert_is_redirect_to(&response, "/login");
+    app.login().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn basic_credentials_cannot_publish_once_enabled() -> Result<()> {
+    // Arrange
+    let app = spawn_app().await?;
+    app.login().await?;
+    enable_two_factor(&app).await?;
+
+    // Act
+    let response = post_newsletters(
+        &app,
+        serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }),
+    )
+    .await?;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let problem: serde_json::Value = response.json().await?;
+    assert_eq!(problem["code"], "two_factor_required");
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.pool)
+        .await?
+        .count;
+    assert_eq!(n_issues, 0);
+    Ok(())
+}